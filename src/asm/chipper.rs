@@ -0,0 +1,635 @@
+// CHIPPER assembler, the syntax used by roms/SOURCES/*.SRC.
+// The first pass lays out addresses & collects labels, the second evaluates operands & encodes.
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::{AsmError, Program, START};
+
+// Mnemonics & directives, anything else at the start of a line is a label
+const KEYWORDS: [&str; 49] = [
+    "CLS", "RET", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SYS", "JP", "CALL", "SE", "SNE",
+    "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP",
+    "SCU", "SAVE", "LOAD", "PLANE", "AUDIO", "PITCH",
+    "DB", "DW", "DA", "EQU", "=", "OPTION", "ALIGN", "DEFINE", "UNDEF", "IFDEF", "IFUND", "ELSE",
+    "ENDIF", "XREF", "USED", "END", "INCLUDE",
+];
+
+// Symbols nest through EQU, give up on anything deeper than this (most likely a cycle)
+const MAX_DEPTH: usize = 32;
+
+enum Symbol {
+    Label(u16),
+    Equ(String), // Expression, evaluated when used so it may refer to later labels
+}
+
+// A line that emits bytes, kept from the first pass for encoding
+struct Statement {
+    line: usize,
+    addr: usize,
+    op: String,
+    args: Vec<String>,
+    schip: bool,  // SCHIP instructions were enabled at this point
+    xochip: bool, // XO-CHIP instructions were enabled at this point
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    V(u16),
+    I,
+    IndirectI, // [I]
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Op(char),
+}
+
+struct Assembler {
+    symbols: HashMap<String, Symbol>,
+    defines: HashSet<String>,
+    statements: Vec<Statement>,
+    addr: usize,
+    align: bool,
+    schip: bool,
+    xochip: bool,
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let mut asm = Assembler {
+        symbols: HashMap::new(),
+        defines: HashSet::new(),
+        statements: Vec::new(),
+        addr: START as usize,
+        align: true,
+        schip: false,
+        xochip: false,
+    };
+    asm.layout(source)?;
+
+    let mut bytes = Vec::new();
+    let mut lines = BTreeMap::new();
+    for st in &asm.statements {
+        lines.insert(st.addr as u16, st.line);
+        let data = asm.encode(st)?;
+        let offset = st.addr - START as usize;
+        if bytes.len() < offset + data.len() {
+            bytes.resize(offset + data.len(), 0);
+        }
+        bytes[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    Ok(Program {
+        bytes,
+        schip: asm.schip,
+        lines,
+    })
+}
+
+// Remove a trailing comment, semicolons inside quotes are kept
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Split the first whitespace separated word from the rest of the line
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, ""),
+    }
+}
+
+// Split operands on commas outside of quotes & parentheses, empty operands are dropped
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                args.push(text[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(text[start..].trim().to_string());
+    args.retain(|a| !a.is_empty());
+    args
+}
+
+// The address of a `LONG NNNN` operand
+fn long_operand(text: &str) -> Option<&str> {
+    let (first, rest) = split_word(text);
+    if first.eq_ignore_ascii_case("LONG") && !rest.is_empty() {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+// 'text' with '' for a literal quote
+fn parse_string(text: &str) -> Option<Vec<u8>> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    Some(inner.replace("''", "'").into_bytes())
+}
+
+fn parse_operand(text: &str) -> Operand {
+    let upper = text.to_uppercase();
+    match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ if upper.len() == 2 && upper.starts_with('V') => match u16::from_str_radix(&upper[1..], 16) {
+            Ok(x) => Operand::V(x),
+            Err(_) => Operand::Expr,
+        },
+        _ => Operand::Expr,
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Numbers: decimal, #hex & $binary where . is a 0 bit
+        let (radix, digits_start) = match c {
+            '#' => (16, i + 1),
+            '$' => (2, i + 1),
+            '0'..='9' => (10, i),
+            _ => (0, i),
+        };
+        if radix != 0 {
+            let mut end = digits_start;
+            while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '.') {
+                end += 1;
+            }
+            let digits: String = chars[digits_start..end]
+                .iter()
+                .map(|&d| if d == '.' && radix == 2 { '0' } else { d })
+                .collect();
+            let value = i64::from_str_radix(&digits, radix)
+                .map_err(|_| format!("Invalid number: {}", chars[i..end].iter().collect::<String>()))?;
+            tokens.push(Token::Num(value));
+            i = end;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = i;
+            while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                end += 1;
+            }
+            tokens.push(Token::Ident(chars[i..end].iter().collect::<String>().to_uppercase()));
+            i = end;
+        } else if "+-*/\\%&|^~<>()!".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return Err(format!("Unexpected character in expression: {}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+// Binary operator precedence, higher binds tighter
+fn precedence(op: char) -> Option<u8> {
+    match op {
+        '<' | '>' => Some(6),
+        '*' | '/' | '%' => Some(5),
+        '+' | '-' => Some(4),
+        '&' => Some(3),
+        '|' | '^' => Some(2),
+        '\\' => Some(1),
+        _ => None,
+    }
+}
+
+impl Assembler {
+    // First pass, resolve conditionals & directives, give every statement & label an address
+    fn layout(&mut self, source: &str) -> Result<(), AsmError> {
+        let mut conditions: Vec<bool> = Vec::new(); // IFDEF nesting, true when assembling
+        let mut pending: Vec<(String, usize)> = Vec::new(); // Labels waiting for the next statement
+
+        for (n, raw) in source.lines().enumerate() {
+            let line = n + 1;
+            let err = |message: String| AsmError::new(line, message);
+            let text = strip_comment(raw).trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            // Labels end with a colon, or are an unknown word followed by a mnemonic
+            let (first, rest) = split_word(text);
+            let (second, after) = split_word(rest);
+            let (label, op, args) = if let Some(label) = first.strip_suffix(':') {
+                (Some(label), second, after)
+            } else if second.eq_ignore_ascii_case("EQU") || second == "=" {
+                (Some(first), "EQU", after)
+            } else if second.is_empty() || KEYWORDS.contains(&second.to_uppercase().as_str()) {
+                if KEYWORDS.contains(&first.to_uppercase().as_str()) {
+                    (None, first, rest)
+                } else {
+                    (Some(first), second, after)
+                }
+            } else {
+                (None, first, rest)
+            };
+            let op = op.to_uppercase();
+            let active = conditions.iter().all(|&c| c);
+
+            match op.as_str() {
+                "IFDEF" | "IFUND" => {
+                    if active {
+                        if let Some(label) = label {
+                            pending.push((label.to_uppercase(), line));
+                        }
+                    }
+                    let defined = self.defines.contains(&args.to_uppercase());
+                    conditions.push(defined == (op == "IFDEF"));
+                    continue;
+                }
+                "ELSE" => {
+                    match conditions.last_mut() {
+                        Some(c) => *c = !*c,
+                        None => return Err(err("ELSE without IFDEF".to_string())),
+                    }
+                    continue;
+                }
+                "ENDIF" => {
+                    if conditions.pop().is_none() {
+                        return Err(err("ENDIF without IFDEF".to_string()));
+                    }
+                    continue;
+                }
+                _ if !active => continue,
+                _ => {}
+            }
+
+            // `NAME = ?` is the current address
+            if op == "EQU" {
+                let name = label.unwrap_or_default().to_uppercase();
+                let symbol = if args == "?" {
+                    Symbol::Label(self.addr as u16)
+                } else {
+                    Symbol::Equ(args.to_string())
+                };
+                self.define(name, symbol, line)?;
+                continue;
+            }
+            if let Some(label) = label {
+                pending.push((label.to_uppercase(), line));
+            }
+
+            match op.as_str() {
+                "" | "XREF" | "USED" => {}
+                "END" => break,
+                "OPTION" => match args.to_uppercase().as_str() {
+                    "BINARY" | "STRING" | "HPHEAD" | "HPASC" => {}
+                    "CHIP8" | "CHIP48" => {
+                        self.schip = false;
+                        self.xochip = false;
+                    }
+                    "SCHIP" | "SCHIP10" | "SCHIP11" => {
+                        self.schip = true;
+                        self.xochip = false;
+                    }
+                    // Not a CHIPPER option, enables the XO-CHIP instructions chip8-disasm writes
+                    "XOCHIP" => {
+                        self.schip = true;
+                        self.xochip = true;
+                    }
+                    _ => return Err(err(format!("Unknown option: {}", args))),
+                },
+                "ALIGN" => match args.to_uppercase().as_str() {
+                    "ON" => self.align = true,
+                    "OFF" => self.align = false,
+                    _ => return Err(err(format!("ALIGN takes ON or OFF, not {}", args))),
+                },
+                "DEFINE" => {
+                    self.defines.insert(args.to_uppercase());
+                }
+                "UNDEF" => {
+                    self.defines.remove(&args.to_uppercase());
+                }
+                "DB" | "DW" | "DA" => {
+                    let args = split_args(args);
+                    let size = match op.as_str() {
+                        "DB" => args.len(),
+                        "DW" => args.len() * 2,
+                        _ => {
+                            let mut size = 0;
+                            for arg in &args {
+                                size += parse_string(arg)
+                                    .ok_or_else(|| err(format!("Invalid string: {}", arg)))?
+                                    .len();
+                            }
+                            size
+                        }
+                    };
+                    self.bind(&mut pending)?;
+                    self.emit(line, op, args, size);
+                }
+                "INCLUDE" => return Err(err("INCLUDE is not supported".to_string())),
+                _ => {
+                    // Instructions start on even addresses unless ALIGN OFF
+                    if self.align && self.addr % 2 == 1 {
+                        self.addr += 1;
+                    }
+                    let args = split_args(args);
+                    let size = if op == "LD" && args.len() == 2 && long_operand(&args[1]).is_some() { 4 } else { 2 };
+                    self.bind(&mut pending)?;
+                    self.emit(line, op, args, size);
+                }
+            }
+        }
+
+        if !conditions.is_empty() {
+            return Err(AsmError::new(source.lines().count(), "Missing ENDIF".to_string()));
+        }
+        self.bind(&mut pending)
+    }
+
+    fn define(&mut self, name: String, symbol: Symbol, line: usize) -> Result<(), AsmError> {
+        if self.symbols.contains_key(&name) {
+            return Err(AsmError::new(line, format!("{} is already defined", name)));
+        }
+        self.symbols.insert(name, symbol);
+        Ok(())
+    }
+
+    // Labels point at the next statement
+    fn bind(&mut self, pending: &mut Vec<(String, usize)>) -> Result<(), AsmError> {
+        for (name, line) in pending.drain(..) {
+            let addr = self.addr as u16;
+            self.define(name, Symbol::Label(addr), line)?;
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, line: usize, op: String, args: Vec<String>, size: usize) {
+        self.statements.push(Statement {
+            line,
+            addr: self.addr,
+            op,
+            args,
+            schip: self.schip,
+            xochip: self.xochip,
+        });
+        self.addr += size;
+    }
+
+    // Second pass, turn a statement into bytes
+    fn encode(&self, st: &Statement) -> Result<Vec<u8>, AsmError> {
+        let err = |message: String| AsmError::new(st.line, message);
+
+        match st.op.as_str() {
+            "DB" => st
+                .args
+                .iter()
+                .map(|arg| self.byte(arg, st.line))
+                .collect::<Result<Vec<u8>, AsmError>>(),
+            "DW" => {
+                let mut bytes = Vec::new();
+                for arg in &st.args {
+                    let value = self.eval(arg, st.line)?;
+                    if !(-0x8000..=0xFFFF).contains(&value) {
+                        return Err(err(format!("Word out of range: {}", value)));
+                    }
+                    bytes.push((value >> 8) as u8);
+                    bytes.push(value as u8);
+                }
+                Ok(bytes)
+            }
+            "DA" => Ok(st.args.iter().flat_map(|arg| parse_string(arg).unwrap_or_default()).collect()),
+            // LD I, LONG NNNN is XO-CHIP's 4 byte F000 NNNN
+            "LD" if st.args.len() == 2 && long_operand(&st.args[1]).is_some() => {
+                if !st.xochip {
+                    return Err(err("LD I, LONG needs OPTION XOCHIP".to_string()));
+                }
+                if parse_operand(&st.args[0]) != Operand::I {
+                    return Err(err(format!("Invalid operands for LD: {}", st.args.join(", "))));
+                }
+                let addr = match self.eval(long_operand(&st.args[1]).unwrap_or_default(), st.line)? {
+                    value @ 0..=0xFFFF => value as u16,
+                    value => return Err(err(format!("Address out of range: {:#X}", value))),
+                };
+                Ok(vec![0xF0, 0x00, (addr >> 8) as u8, addr as u8])
+            }
+            _ => {
+                let opcode = self.instruction(st)?;
+                Ok(vec![(opcode >> 8) as u8, opcode as u8])
+            }
+        }
+    }
+
+    fn instruction(&self, st: &Statement) -> Result<u16, AsmError> {
+        use self::Operand::*;
+
+        let line = st.line;
+        let ops: Vec<Operand> = st.args.iter().map(|a| parse_operand(a)).collect();
+        let arg = |n: usize| st.args[n].as_str();
+        let schip = |opcode: u16| {
+            if st.schip {
+                Ok(opcode)
+            } else {
+                Err(AsmError::new(line, format!("{} needs OPTION SCHIP", st.op)))
+            }
+        };
+        let xochip = |opcode: u16| {
+            if st.xochip {
+                Ok(opcode)
+            } else {
+                Err(AsmError::new(line, format!("{} needs OPTION XOCHIP", st.op)))
+            }
+        };
+
+        let opcode = match (st.op.as_str(), ops.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SCD", [Expr]) => schip(0x00C0 | self.nibble(arg(0), line)?)?,
+            ("SCR", []) => schip(0x00FB)?,
+            ("SCL", []) => schip(0x00FC)?,
+            ("EXIT", []) => schip(0x00FD)?,
+            ("LOW", []) => schip(0x00FE)?,
+            ("HIGH", []) => schip(0x00FF)?,
+            ("SYS", [Expr]) => self.address(arg(0), line)?,
+            ("JP", [Expr]) => 0x1000 | self.address(arg(0), line)?,
+            ("JP", [V(0), Expr]) => 0xB000 | self.address(arg(1), line)?,
+            ("CALL", [Expr]) => 0x2000 | self.address(arg(0), line)?,
+            ("SE", [V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+            ("SE", [V(x), Expr]) => 0x3000 | x << 8 | self.byte(arg(1), line)? as u16,
+            ("SNE", [V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+            ("SNE", [V(x), Expr]) => 0x4000 | x << 8 | self.byte(arg(1), line)? as u16,
+            ("LD", [V(x), V(y)]) => 0x8000 | x << 8 | y << 4,
+            ("LD", [V(x), Dt]) => 0xF007 | x << 8,
+            ("LD", [V(x), K]) => 0xF00A | x << 8,
+            ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+            ("LD", [V(x), R]) => schip(0xF085 | x << 8)?,
+            ("LD", [V(x), Expr]) => 0x6000 | x << 8 | self.byte(arg(1), line)? as u16,
+            ("LD", [I, Expr]) => 0xA000 | self.address(arg(1), line)?,
+            ("LD", [Dt, V(x)]) => 0xF015 | x << 8,
+            ("LD", [St, V(x)]) => 0xF018 | x << 8,
+            ("LD", [F, V(x)]) => 0xF029 | x << 8,
+            ("LD", [Hf, V(x)]) => schip(0xF030 | x << 8)?,
+            ("LD", [B, V(x)]) => 0xF033 | x << 8,
+            ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+            ("LD", [R, V(x)]) => schip(0xF075 | x << 8)?,
+            ("ADD", [V(x), V(y)]) => 0x8004 | x << 8 | y << 4,
+            ("ADD", [V(x), Expr]) => 0x7000 | x << 8 | self.byte(arg(1), line)? as u16,
+            ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+            ("OR", [V(x), V(y)]) => 0x8001 | x << 8 | y << 4,
+            ("AND", [V(x), V(y)]) => 0x8002 | x << 8 | y << 4,
+            ("XOR", [V(x), V(y)]) => 0x8003 | x << 8 | y << 4,
+            ("SUB", [V(x), V(y)]) => 0x8005 | x << 8 | y << 4,
+            ("SHR", [V(x)]) => 0x8006 | x << 8,
+            ("SHR", [V(x), V(y)]) => 0x8006 | x << 8 | y << 4,
+            ("SUBN", [V(x), V(y)]) => 0x8007 | x << 8 | y << 4,
+            ("SHL", [V(x)]) => 0x800E | x << 8,
+            ("SHL", [V(x), V(y)]) => 0x800E | x << 8 | y << 4,
+            ("RND", [V(x), Expr]) => 0xC000 | x << 8 | self.byte(arg(1), line)? as u16,
+            ("DRW", [V(x), V(y), Expr]) => 0xD000 | x << 8 | y << 4 | self.nibble(arg(2), line)?,
+            ("SKP", [V(x)]) => 0xE09E | x << 8,
+            ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+            ("SCU", [Expr]) => xochip(0x00D0 | self.nibble(arg(0), line)?)?,
+            ("SAVE", [V(x), V(y)]) => xochip(0x5002 | x << 8 | y << 4)?,
+            ("LOAD", [V(x), V(y)]) => xochip(0x5003 | x << 8 | y << 4)?,
+            ("PLANE", [Expr]) => xochip(0xF001 | self.nibble(arg(0), line)? << 8)?,
+            ("AUDIO", []) => xochip(0xF002)?,
+            ("PITCH", [V(x)]) => xochip(0xF03A | x << 8)?,
+            _ if KEYWORDS.contains(&st.op.as_str()) => {
+                return Err(AsmError::new(line, format!("Invalid operands for {}: {}", st.op, st.args.join(", "))))
+            }
+            _ => return Err(AsmError::new(line, format!("Unknown instruction: {}", st.op))),
+        };
+        Ok(opcode)
+    }
+
+    fn address(&self, text: &str, line: usize) -> Result<u16, AsmError> {
+        match self.eval(text, line)? {
+            value @ 0..=0xFFF => Ok(value as u16),
+            value => Err(AsmError::new(line, format!("Address out of range: {:#X}", value))),
+        }
+    }
+
+    // Bytes may be written as negative numbers, -1 is #FF
+    fn byte(&self, text: &str, line: usize) -> Result<u8, AsmError> {
+        match self.eval(text, line)? {
+            value @ -0x80..=0xFF => Ok(value as u8),
+            value => Err(AsmError::new(line, format!("Byte out of range: {}", value))),
+        }
+    }
+
+    fn nibble(&self, text: &str, line: usize) -> Result<u16, AsmError> {
+        match self.eval(text, line)? {
+            value @ 0..=0xF => Ok(value as u16),
+            value => Err(AsmError::new(line, format!("Nibble out of range: {}", value))),
+        }
+    }
+
+    fn eval(&self, text: &str, line: usize) -> Result<i64, AsmError> {
+        self.eval_depth(text, 0).map_err(|message| AsmError::new(line, message))
+    }
+
+    fn eval_depth(&self, text: &str, depth: usize) -> Result<i64, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("Symbols nested too deeply in: {}", text));
+        }
+        let tokens = tokenize(text)?;
+        let mut pos = 0;
+        let value = self.expr(&tokens, &mut pos, 0, depth)?;
+        if pos != tokens.len() {
+            return Err(format!("Invalid expression: {}", text));
+        }
+        Ok(value)
+    }
+
+    // Precedence climbing over binary operators
+    fn expr(&self, tokens: &[Token], pos: &mut usize, min: u8, depth: usize) -> Result<i64, String> {
+        let mut lhs = self.unary(tokens, pos, depth)?;
+
+        while let Some(&Token::Op(op)) = tokens.get(*pos) {
+            let prec = match precedence(op) {
+                Some(prec) if prec >= min => prec,
+                _ => break,
+            };
+            *pos += 1;
+            let rhs = self.expr(tokens, pos, prec + 1, depth)?;
+            lhs = match op {
+                '<' => lhs << rhs,
+                '>' => lhs >> rhs,
+                '*' => lhs * rhs,
+                '/' | '\\' if rhs == 0 => return Err("Division by zero".to_string()),
+                '/' | '\\' => lhs / rhs,
+                '%' if rhs == 0 => return Err("Division by zero".to_string()),
+                '%' => lhs % rhs,
+                '+' => lhs + rhs,
+                '-' => lhs - rhs,
+                '&' => lhs & rhs,
+                '|' => lhs | rhs,
+                _ => lhs ^ rhs,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&self, tokens: &[Token], pos: &mut usize, depth: usize) -> Result<i64, String> {
+        let token = tokens.get(*pos).cloned().ok_or("Missing operand")?;
+        *pos += 1;
+
+        match token {
+            Token::Num(value) => Ok(value),
+            Token::Ident(name) => match self.symbols.get(&name) {
+                Some(Symbol::Label(addr)) => Ok(*addr as i64),
+                Some(Symbol::Equ(text)) => self.eval_depth(text, depth + 1),
+                None => Err(format!("Undefined symbol: {}", name)),
+            },
+            Token::Op('-') => Ok(-self.unary(tokens, pos, depth)?),
+            Token::Op('+') => self.unary(tokens, pos, depth),
+            Token::Op('~') => Ok(!self.unary(tokens, pos, depth)?),
+            Token::Op('(') => {
+                let value = self.expr(tokens, pos, 0, depth)?;
+                match tokens.get(*pos) {
+                    Some(Token::Op(')')) => {
+                        *pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err("Missing )".to_string()),
+                }
+            }
+            Token::Op(op) => Err(format!("Unexpected {} in expression", op)),
+        }
+    }
+}
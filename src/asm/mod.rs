@@ -0,0 +1,99 @@
+// Assemblers for CHIP-8 source code, programs are assembled to run from 0x200
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+pub mod chipper;
+pub mod octo;
+
+// ROMs are loaded at 0x200
+pub const START: u16 = 0x200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub bytes: Vec<u8>,
+    pub schip: bool,                 // Assembled for SCHIP, written out as .sc8
+    pub lines: BTreeMap<u16, usize>, // Address of each statement -> source line
+}
+
+impl Program {
+    // Conventional file extension for the assembled binary
+    pub fn extension(&self) -> &'static str {
+        if self.schip {
+            "sc8"
+        } else {
+            "ch8"
+        }
+    }
+}
+
+// Maps addresses back to source lines for the debugger & error messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceMap {
+    pub file: String,
+    pub lines: BTreeMap<u16, usize>,
+}
+
+impl SourceMap {
+    pub fn new(path: &str, program: &Program) -> Self {
+        let file = Path::new(path)
+            .file_name()
+            .map_or_else(|| path.to_string(), |name| name.to_string_lossy().into_owned());
+        SourceMap {
+            file,
+            lines: program.lines.clone(),
+        }
+    }
+
+    // Line of the statement that covers addr
+    pub fn line(&self, addr: u16) -> Option<usize> {
+        self.lines.range(..=addr).next_back().map(|(_, &line)| line)
+    }
+
+    // First address generated by the given line, or the next line that generated any code
+    pub fn addr(&self, line: usize) -> Option<u16> {
+        self.lines
+            .iter()
+            .filter(|&(_, &l)| l >= line)
+            .min_by_key(|&(&addr, &l)| (l, addr))
+            .map(|(&addr, _)| addr)
+    }
+}
+
+// Source files are recognised by extension, everything else is a binary ROM
+pub fn is_source(path: &str) -> bool {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ["src", "8o", "asm"].contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+// Assemble Octo for .8o files & CHIPPER for everything else
+pub fn assemble(path: &str, source: &str) -> Result<Program, AsmError> {
+    if path.to_lowercase().ends_with(".8o") {
+        octo::assemble(source)
+    } else {
+        chipper::assemble(source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    pub fn new(line: usize, message: String) -> Self {
+        AsmError { line, message }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
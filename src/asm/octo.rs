@@ -0,0 +1,783 @@
+// Octo assembler (https://github.com/JohnEarnest/Octo), including the XO-CHIP instructions.
+// Like Octo this is a single pass over the tokens, forward references are patched at the end.
+use std::collections::{BTreeMap, HashMap};
+
+use super::{AsmError, Program, START};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    line: usize,
+    depth: usize, // Macro expansions this token came out of, 0 for source
+}
+
+// Macros expanding macros give up past this depth, most likely a macro that uses itself
+const MAX_DEPTH: usize = 32;
+
+// How to fill in a forward reference once the label is known
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Patch {
+    Addr,   // Low 12 bits of an instruction
+    Long,   // 16 bit word after F000
+    Unpack, // v0 := hi nibble, v1 := lo byte from :unpack
+}
+
+struct Compiler {
+    tokens: Vec<Token>, // Reversed so the next token is popped off the end
+    rom: Vec<u8>,
+    lines: BTreeMap<u16, usize>,
+    here: usize,
+    line: usize,
+    depth: usize, // Macro depth of the last token read
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u16>,
+    macros: HashMap<String, (Vec<String>, Vec<Token>)>,
+    patches: Vec<(usize, String, Patch, usize)>,
+    branches: Vec<usize>,            // if ... begin jumps waiting for else or end
+    loops: Vec<(usize, Vec<usize>)>, // loop start & while jumps waiting for again
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    // Execution starts at main, which needs a jump unless it's the first thing in the program
+    let program = compile(source, false)?;
+    if program.labels.get("main") == Some(&START) {
+        return Ok(program.finish());
+    }
+    Ok(compile(source, true)?.finish())
+}
+
+fn compile(source: &str, jump_main: bool) -> Result<Compiler, AsmError> {
+    let mut c = Compiler {
+        tokens: tokenize(source),
+        rom: Vec::new(),
+        lines: BTreeMap::new(),
+        here: START as usize,
+        line: 1,
+        depth: 0,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        patches: Vec::new(),
+        branches: Vec::new(),
+        loops: Vec::new(),
+    };
+    if jump_main {
+        c.reference("main", Patch::Addr, 0x1000)?;
+    }
+
+    while !c.tokens.is_empty() {
+        let (here, line) = (c.here, c.tokens[c.tokens.len() - 1].line);
+        c.statement()?;
+        if c.here != here {
+            c.lines.insert(here as u16, line);
+        }
+    }
+
+    if !c.branches.is_empty() {
+        return Err(c.error("Missing end for if ... begin".to_string()));
+    }
+    if !c.loops.is_empty() {
+        return Err(c.error("Missing again for loop".to_string()));
+    }
+    if !c.labels.contains_key("main") {
+        return Err(c.error("The program has no main label".to_string()));
+    }
+
+    for (addr, name, patch, line) in c.patches.clone() {
+        let target = match c.labels.get(&name) {
+            Some(&target) => target,
+            None => return Err(AsmError::new(line, format!("Undefined name: {}", name))),
+        };
+        if patch != Patch::Long && target > 0xFFF {
+            return Err(AsmError::new(line, format!("{} is out of range, use i := long", name)));
+        }
+        let i = addr - START as usize;
+        match patch {
+            Patch::Addr => {
+                c.rom[i] |= (target >> 8) as u8 & 0x0F;
+                c.rom[i + 1] = target as u8;
+            }
+            Patch::Long => {
+                c.rom[i] = (target >> 8) as u8;
+                c.rom[i + 1] = target as u8;
+            }
+            Patch::Unpack => {
+                c.rom[i + 1] |= (target >> 8) as u8 & 0x0F;
+                c.rom[i + 3] = target as u8;
+            }
+        }
+    }
+    Ok(c)
+}
+
+// Whitespace separated tokens, # starts a comment
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let code = match line.find('#') {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        for text in code.split_whitespace() {
+            tokens.push(Token {
+                text: text.to_string(),
+                line: n + 1,
+                depth: 0,
+            });
+        }
+    }
+    tokens.reverse();
+    tokens
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse::<f64>().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn register(text: &str) -> Option<u16> {
+    let rest = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+    if rest.len() == 1 {
+        u16::from_str_radix(rest, 16).ok()
+    } else {
+        None
+    }
+}
+
+impl Compiler {
+    fn finish(self) -> Program {
+        Program {
+            bytes: self.rom,
+            schip: false,
+            lines: self.lines,
+        }
+    }
+
+    fn error(&self, message: String) -> AsmError {
+        AsmError::new(self.line, message)
+    }
+
+    fn next(&mut self) -> Result<String, AsmError> {
+        match self.tokens.pop() {
+            Some(token) => {
+                self.line = token.line;
+                self.depth = token.depth;
+                Ok(token.text)
+            }
+            None => Err(self.error("Unexpected end of file".to_string())),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.last().map(|t| t.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if token == text {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected {}, found {}", text, token)))
+        }
+    }
+
+    fn emit(&mut self, byte: u8) {
+        let i = self.here - START as usize;
+        if self.rom.len() <= i {
+            self.rom.resize(i + 1, 0);
+        }
+        self.rom[i] = byte;
+        self.here += 1;
+    }
+
+    fn inst(&mut self, opcode: u16) {
+        self.emit((opcode >> 8) as u8);
+        self.emit(opcode as u8);
+    }
+
+    // Emit an instruction addressing a label, patched later if it isn't defined yet
+    fn reference(&mut self, name: &str, patch: Patch, opcode: u16) -> Result<(), AsmError> {
+        let addr = match self.labels.get(name) {
+            Some(&addr) => Some(addr),
+            None => match self.constant(name) {
+                Some(value) if patch == Patch::Long && (0.0..65536.0).contains(&value) => Some(value as u16),
+                Some(value) => Some(self.address(value)?),
+                None => None,
+            },
+        };
+
+        let addr = match addr {
+            Some(addr) => addr,
+            None if name.starts_with(|c: char| c.is_alphabetic() || c == '_') => {
+                let at = if patch == Patch::Long { self.here + 2 } else { self.here };
+                self.patches.push((at, name.to_string(), patch, self.line));
+                0
+            }
+            None => return Err(self.error(format!("Invalid name: {}", name))),
+        };
+        if patch == Patch::Long {
+            self.inst(opcode);
+            self.inst(addr);
+        } else {
+            self.inst(opcode | addr);
+        }
+        Ok(())
+    }
+
+    // Numbers & :const / :calc values, labels are not constants
+    fn constant(&self, text: &str) -> Option<f64> {
+        parse_number(text).or_else(|| self.constants.get(text).cloned())
+    }
+
+    fn address(&self, value: f64) -> Result<u16, AsmError> {
+        let addr = value as i64;
+        if (0..=0xFFF).contains(&addr) {
+            Ok(addr as u16)
+        } else {
+            Err(self.error(format!("Address out of range: {:#X}", addr)))
+        }
+    }
+
+    fn reg(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.reg_from(&token)
+    }
+
+    fn reg_from(&self, token: &str) -> Result<u16, AsmError> {
+        match register(token).or_else(|| self.aliases.get(token).cloned()) {
+            Some(x) => Ok(x),
+            None => Err(self.error(format!("Expected a register, found {}", token))),
+        }
+    }
+
+    fn is_reg(&self, token: &str) -> bool {
+        register(token).is_some() || self.aliases.contains_key(token)
+    }
+
+    // A byte value, negative numbers wrap so -1 is 0xFF
+    fn byte(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        let value = match self.constant(&token) {
+            Some(value) => value as i64,
+            None => match self.labels.get(&token) {
+                Some(&addr) => addr as i64,
+                None => return Err(self.error(format!("Expected a number, found {}", token))),
+            },
+        };
+        if (-128..=255).contains(&value) {
+            Ok(value as u8 as u16)
+        } else {
+            Err(self.error(format!("Byte out of range: {}", value)))
+        }
+    }
+
+    fn nibble(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        match self.constant(&token) {
+            Some(value) if (0.0..16.0).contains(&value) => Ok(value as u16),
+            _ => Err(self.error(format!("Expected a number from 0 to 15, found {}", token))),
+        }
+    }
+
+    fn label(&mut self, name: String, addr: usize) -> Result<(), AsmError> {
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+            return Err(self.error(format!("{} is already defined", name)));
+        }
+        self.labels.insert(name, addr as u16);
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+
+        match token.as_str() {
+            ":" => {
+                let name = self.next()?;
+                let here = self.here;
+                self.label(name, here)?;
+            }
+            ":next" => {
+                let name = self.next()?;
+                let here = self.here + 1;
+                self.label(name, here)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                let token = self.next()?;
+                match self.constant(&token) {
+                    Some(value) => {
+                        self.constants.insert(name, value);
+                    }
+                    None => return Err(self.error(format!("Expected a number, found {}", token))),
+                }
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let x = self.reg()?;
+                self.aliases.insert(name, x);
+            }
+            ":unpack" => {
+                let hi = self.nibble()?;
+                let name = self.next()?;
+                match self.labels.get(&name).cloned().or_else(|| self.constant(&name).map(|v| v as u16)) {
+                    Some(addr) => {
+                        self.inst(0x6000 | hi << 4 | (addr >> 8) & 0x0F);
+                        self.inst(0x6100 | addr & 0xFF);
+                    }
+                    None => {
+                        self.patches.push((self.here, name, Patch::Unpack, self.line));
+                        self.inst(0x6000 | hi << 4);
+                        self.inst(0x6100);
+                    }
+                }
+            }
+            ":org" => {
+                let token = self.next()?;
+                match self.constant(&token) {
+                    Some(value) if value as usize >= START as usize && value < 65536.0 => {
+                        self.here = value as usize
+                    }
+                    _ => return Err(self.error(format!("Invalid :org address: {}", token))),
+                }
+            }
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.next()?;
+                    self.calc()?
+                } else {
+                    self.byte()? as f64
+                };
+                self.emit(value as i64 as u8);
+            }
+            ":macro" => {
+                let name = self.next()?;
+                let mut args = Vec::new();
+                loop {
+                    let token = self.next()?;
+                    if token == "{" {
+                        break;
+                    }
+                    args.push(token);
+                }
+                let mut body = Vec::new();
+                let mut depth = 1;
+                loop {
+                    let line = self.line;
+                    let text = self.next()?;
+                    match text.as_str() {
+                        "{" => depth += 1,
+                        "}" => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    body.push(Token { text, line, depth: 0 });
+                }
+                self.macros.insert(name, (args, body));
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "return" | ";" => self.inst(0x00EE),
+            "clear" => self.inst(0x00E0),
+            "hires" => self.inst(0x00FF),
+            "lores" => self.inst(0x00FE),
+            "exit" => self.inst(0x00FD),
+            "scroll-left" => self.inst(0x00FC),
+            "scroll-right" => self.inst(0x00FB),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.inst(0x00C0 | n);
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.inst(0x00D0 | n);
+            }
+            "bcd" => {
+                let x = self.reg()?;
+                self.inst(0xF033 | x << 8);
+            }
+            "save" | "load" => {
+                let x = self.reg()?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.reg()?;
+                    let op = if token == "save" { 0x5002 } else { 0x5003 };
+                    self.inst(op | x << 8 | y << 4);
+                } else {
+                    let op = if token == "save" { 0xF055 } else { 0xF065 };
+                    self.inst(op | x << 8);
+                }
+            }
+            "saveflags" => {
+                let x = self.reg()?;
+                self.inst(0xF075 | x << 8);
+            }
+            "loadflags" => {
+                let x = self.reg()?;
+                self.inst(0xF085 | x << 8);
+            }
+            "sprite" => {
+                let x = self.reg()?;
+                let y = self.reg()?;
+                let n = self.nibble()?;
+                self.inst(0xD000 | x << 8 | y << 4 | n);
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.inst(0xF001 | n << 8);
+            }
+            "audio" => self.inst(0xF002),
+            "jump" | "jump0" | "native" => {
+                let name = self.next()?;
+                let op = match token.as_str() {
+                    "jump" => 0x1000,
+                    "jump0" => 0xB000,
+                    _ => 0x0000,
+                };
+                self.reference(&name, Patch::Addr, op)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.reg()?;
+                let op = match token.as_str() {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.inst(op | x << 8);
+            }
+            "i" => self.index()?,
+            "if" => {
+                self.condition(false)?;
+                match self.next()?.as_str() {
+                    "then" => {}
+                    "begin" => {
+                        // Skip the jump to else / end when the condition holds
+                        self.invert_skip();
+                        self.branches.push(self.here);
+                        self.inst(0x1000);
+                    }
+                    other => return Err(self.error(format!("Expected then or begin, found {}", other))),
+                }
+            }
+            "else" => {
+                let branch = self.branches.pop().ok_or_else(|| self.error("else without if ... begin".to_string()))?;
+                let jump = self.here;
+                self.inst(0x1000);
+                self.patch_here(branch);
+                self.branches.push(jump);
+            }
+            "end" => {
+                let branch = self.branches.pop().ok_or_else(|| self.error("end without if ... begin".to_string()))?;
+                self.patch_here(branch);
+            }
+            "loop" => self.loops.push((self.here, Vec::new())),
+            "while" => {
+                // Skip the exit jump while the condition holds
+                self.condition(true)?;
+                let jump = self.here;
+                self.inst(0x1000);
+                match self.loops.last_mut() {
+                    Some((_, breaks)) => breaks.push(jump),
+                    None => return Err(self.error("while outside of a loop".to_string())),
+                }
+            }
+            "again" => {
+                let (start, breaks) = self.loops.pop().ok_or_else(|| self.error("again without loop".to_string()))?;
+                self.inst(0x1000 | start as u16);
+                for jump in breaks {
+                    self.patch_here(jump);
+                }
+            }
+            _ if self.is_reg(&token) => self.assign(&token)?,
+            _ if self.macros.contains_key(&token) => self.expand(&token)?,
+            _ => {
+                // Numbers & constants are data, anything else is a subroutine call
+                match self.constant(&token) {
+                    Some(value) => {
+                        let value = value as i64;
+                        if !(-128..=255).contains(&value) {
+                            return Err(self.error(format!("Byte out of range: {}", value)));
+                        }
+                        self.emit(value as u8);
+                    }
+                    None => self.reference(&token, Patch::Addr, 0x2000)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Turn the skip instruction just emitted into its opposite, SE <-> SNE & SKP <-> SKNP
+    fn invert_skip(&mut self) {
+        let i = self.here - START as usize - 2;
+        match self.rom[i] & 0xF0 {
+            0x30 | 0x40 => self.rom[i] ^= 0x70,
+            0x50 | 0x90 => self.rom[i] ^= 0xC0,
+            _ => self.rom[i + 1] ^= 0x9E ^ 0xA1,
+        }
+    }
+
+    // Point the jump at addr to the current address
+    fn patch_here(&mut self, addr: usize) {
+        let i = addr - START as usize;
+        self.rom[i] = 0x10 | (self.here >> 8) as u8 & 0x0F;
+        self.rom[i + 1] = self.here as u8;
+    }
+
+    fn index(&mut self) -> Result<(), AsmError> {
+        let op = self.next()?;
+        match op.as_str() {
+            ":=" => {
+                let token = self.next()?;
+                match token.as_str() {
+                    "long" => {
+                        let name = self.next()?;
+                        self.reference(&name, Patch::Long, 0xF000)?;
+                    }
+                    "hex" => {
+                        let x = self.reg()?;
+                        self.inst(0xF029 | x << 8);
+                    }
+                    "bighex" => {
+                        let x = self.reg()?;
+                        self.inst(0xF030 | x << 8);
+                    }
+                    _ => self.reference(&token, Patch::Addr, 0xA000)?,
+                }
+            }
+            "+=" => {
+                let x = self.reg()?;
+                self.inst(0xF01E | x << 8);
+            }
+            _ => return Err(self.error(format!("Unknown operator for i: {}", op))),
+        }
+        Ok(())
+    }
+
+    // vx := ..., vx += ... & friends
+    fn assign(&mut self, target: &str) -> Result<(), AsmError> {
+        let x = self.reg_from(target)?;
+        let op = self.next()?;
+        let rhs = self.peek().unwrap_or_default().to_string();
+
+        if self.is_reg(&rhs) {
+            self.next()?;
+            let y = self.reg_from(&rhs)?;
+            let n = match op.as_str() {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(self.error(format!("Unknown operator: {}", op))),
+            };
+            self.inst(0x8000 | x << 8 | y << 4 | n);
+            return Ok(());
+        }
+
+        match (op.as_str(), rhs.as_str()) {
+            (":=", "delay") => {
+                self.next()?;
+                self.inst(0xF007 | x << 8);
+            }
+            (":=", "key") => {
+                self.next()?;
+                self.inst(0xF00A | x << 8);
+            }
+            (":=", "random") => {
+                self.next()?;
+                let n = self.byte()?;
+                self.inst(0xC000 | x << 8 | n);
+            }
+            (":=", _) => {
+                let n = self.byte()?;
+                self.inst(0x6000 | x << 8 | n);
+            }
+            ("+=", _) => {
+                let n = self.byte()?;
+                self.inst(0x7000 | x << 8 | n);
+            }
+            ("-=", _) => {
+                let n = self.byte()?;
+                self.inst(0x7000 | x << 8 | (n as u8).wrapping_neg() as u16);
+            }
+            _ => return Err(self.error(format!("Unknown operator: {}", op))),
+        }
+        Ok(())
+    }
+
+    // Emit instructions that skip the next one when the condition is true (while) or false (if)
+    fn condition(&mut self, skip_when_true: bool) -> Result<(), AsmError> {
+        let x = self.reg()?;
+        let op = self.next()?;
+
+        if op == "key" || op == "-key" {
+            let pressed = op == "key";
+            let opcode = if pressed == skip_when_true { 0xE09E } else { 0xE0A1 };
+            self.inst(opcode | x << 8);
+            return Ok(());
+        }
+
+        let rhs = self.next()?;
+        let (x, op, rhs) = match op.as_str() {
+            "==" | "!=" => (x, op, rhs),
+            "<" | ">" | "<=" | ">=" => {
+                // vf := lhs - rhs, VF ends up 0 on a borrow. < & > hold on a borrow, <= & >= don't
+                let swap = op == ">" || op == "<=";
+                if self.is_reg(&rhs) {
+                    let y = self.reg_from(&rhs)?;
+                    let (a, b) = if swap { (y, x) } else { (x, y) };
+                    self.inst(0x8F00 | a << 4);
+                    self.inst(0x8F05 | b << 4);
+                } else {
+                    self.tokens.push(Token { text: rhs, line: self.line, depth: self.depth });
+                    let n = self.byte()?;
+                    self.inst(0x6F00 | n);
+                    self.inst(if swap { 0x8F05 | x << 4 } else { 0x8F07 | x << 4 });
+                }
+                let borrow = op == "<" || op == ">";
+                (0xF, "==".to_string(), if borrow { "0" } else { "1" }.to_string())
+            }
+            _ => return Err(self.error(format!("Unknown comparison: {}", op))),
+        };
+
+        // SE skips when equal, SNE when not equal
+        let skip_when_equal = (op == "==") == skip_when_true;
+        if self.is_reg(&rhs) {
+            let y = self.reg_from(&rhs)?;
+            let opcode = if skip_when_equal { 0x5000 } else { 0x9000 };
+            self.inst(opcode | x << 8 | y << 4);
+        } else {
+            self.tokens.push(Token { text: rhs, line: self.line, depth: self.depth });
+            let n = self.byte()?;
+            let opcode = if skip_when_equal { 0x3000 } else { 0x4000 };
+            self.inst(opcode | x << 8 | n);
+        }
+        Ok(())
+    }
+
+    // Substitute the arguments into the macro body & push it back onto the token stream
+    fn expand(&mut self, name: &str) -> Result<(), AsmError> {
+        let depth = self.depth + 1;
+        if depth > MAX_DEPTH {
+            return Err(self.error(format!("Macros nested too deeply in: {}", name)));
+        }
+        let (params, body) = self.macros[name].clone();
+        let mut args = HashMap::new();
+        for param in params {
+            let arg = self.next()?;
+            args.insert(param, arg);
+        }
+        let line = self.line;
+        for token in body.into_iter().rev() {
+            let text = args.get(&token.text).cloned().unwrap_or(token.text);
+            self.tokens.push(Token { text, line, depth });
+        }
+        Ok(())
+    }
+
+    // :calc expressions are evaluated right to left without operator precedence, like Octo.
+    // Consumes tokens up to the closing brace.
+    fn calc(&mut self) -> Result<f64, AsmError> {
+        let value = self.calc_expr()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    fn calc_expr(&mut self) -> Result<f64, AsmError> {
+        let lhs = self.calc_term()?;
+        let op = match self.peek() {
+            Some(op) if op != "}" && op != ")" => op.to_string(),
+            _ => return Ok(lhs),
+        };
+        self.next()?;
+        let rhs = self.calc_expr()?;
+        let bool = |b: bool| if b { 1.0 } else { 0.0 };
+        let int = |v: f64| v as i64;
+        Ok(match op.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => (int(lhs) & int(rhs)) as f64,
+            "|" => (int(lhs) | int(rhs)) as f64,
+            "^" => (int(lhs) ^ int(rhs)) as f64,
+            "<<" => (int(lhs) << int(rhs)) as f64,
+            ">>" => (int(lhs) >> int(rhs)) as f64,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "<" => bool(lhs < rhs),
+            "<=" => bool(lhs <= rhs),
+            "==" => bool(lhs == rhs),
+            "!=" => bool(lhs != rhs),
+            ">=" => bool(lhs >= rhs),
+            ">" => bool(lhs > rhs),
+            _ => return Err(self.error(format!("Unknown operator in :calc: {}", op))),
+        })
+    }
+
+    fn calc_term(&mut self) -> Result<f64, AsmError> {
+        let token = self.next()?;
+        let unary = |f: fn(f64) -> f64, c: &mut Compiler| c.calc_term().map(f);
+        match token.as_str() {
+            "(" => {
+                let value = self.calc_expr()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "-" => unary(|v| -v, self),
+            "~" => unary(|v| !(v as i64) as f64, self),
+            "!" => unary(|v| if v == 0.0 { 1.0 } else { 0.0 }, self),
+            "abs" => unary(f64::abs, self),
+            "sqrt" => unary(f64::sqrt, self),
+            "sin" => unary(f64::sin, self),
+            "cos" => unary(f64::cos, self),
+            "tan" => unary(f64::tan, self),
+            "exp" => unary(f64::exp, self),
+            "log" => unary(f64::ln, self),
+            "sign" => unary(f64::signum, self),
+            "ceil" => unary(f64::ceil, self),
+            "floor" => unary(f64::floor, self),
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => match self.constant(&token).or_else(|| self.labels.get(&token).map(|&a| a as f64)) {
+                Some(value) => Ok(value),
+                None => Err(self.error(format!("Unknown name in :calc: {}", token))),
+            },
+        }
+    }
+}
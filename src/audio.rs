@@ -0,0 +1,111 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::Sdl;
+
+pub const FREQUENCY: f32 = 440.0;
+pub const VOLUME: f32 = 0.25;
+
+// Square wave tone, or the XO-CHIP 128 bit audio pattern once a ROM has loaded one
+pub struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+    sample_rate: f32,
+    pattern: [u8; 16],
+    pattern_inc: f32,
+    pattern_pos: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        let use_pattern = self.pattern.iter().any(|&b| b != 0);
+
+        for x in out.iter_mut() {
+            let high = if use_pattern {
+                let bit = self.pattern_pos as usize;
+                self.pattern_pos = (self.pattern_pos + self.pattern_inc) % 128.0;
+                self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0
+            } else {
+                self.phase = (self.phase + self.phase_inc) % 1.0;
+                self.phase < 0.5
+            };
+
+            *x = if high { self.volume } else { -self.volume };
+        }
+    }
+}
+
+// Square wave beeper, audible while the sound timer is non-zero
+pub struct Beeper {
+    device: Option<AudioDevice<SquareWave>>,
+    pub muted: bool,
+    playing: bool,
+}
+
+impl Beeper {
+    pub fn new(sdl_context: &Sdl, frequency: f32, volume: f32) -> Beeper {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(1),
+            samples: None,
+        };
+
+        // Keep running without sound if there is no usable audio device
+        let device = sdl_context
+            .audio()
+            .and_then(|audio| {
+                audio.open_playback(None, &desired_spec, |spec| SquareWave {
+                    phase_inc: frequency / spec.freq as f32,
+                    phase: 0.0,
+                    volume,
+                    sample_rate: spec.freq as f32,
+                    pattern: [0; 16],
+                    pattern_inc: 0.0,
+                    pattern_pos: 0.0,
+                })
+            })
+            .map_err(|e| println!("Audio disabled: {}", e))
+            .ok();
+
+        Beeper {
+            device,
+            muted: false,
+            playing: false,
+        }
+    }
+
+    // Start or stop the tone, only touching the device when the state changes
+    pub fn update(&mut self, beeping: bool) {
+        let play = beeping && !self.muted;
+        if play == self.playing {
+            return;
+        }
+
+        if let Some(ref device) = self.device {
+            if play {
+                device.resume();
+            } else {
+                device.pause();
+            }
+        }
+        self.playing = play;
+    }
+
+    // Hand the XO-CHIP pattern buffer & playback rate (bits per second) to the audio thread
+    pub fn set_pattern(&mut self, pattern: &[u8; 16], rate: f32) {
+        if let Some(ref mut device) = self.device {
+            let mut wave = device.lock();
+            if wave.pattern != *pattern {
+                wave.pattern = *pattern;
+                wave.pattern_pos = 0.0;
+            }
+            wave.pattern_inc = rate / wave.sample_rate;
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        println!("Mute: {}", self.muted);
+    }
+}
@@ -0,0 +1,43 @@
+// chip8-asm: assemble CHIPPER or Octo (.8o) source into a ROM, written next to the source as .ch8 or .sc8
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use chip8::asm;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        println!("Usage: chip8-asm [Path to source] [Output rom]");
+        process::exit(2);
+    }
+
+    let source = match fs::read_to_string(&args[1]) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}", args[1], e);
+            process::exit(1);
+        }
+    };
+    let program = match asm::assemble(&args[1], &source) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}:{}: {}", args[1], e.line, e.message);
+            process::exit(1);
+        }
+    };
+
+    let output = match args.get(2) {
+        Some(output) => output.clone(),
+        None => Path::new(&args[1])
+            .with_extension(program.extension())
+            .to_string_lossy()
+            .into_owned(),
+    };
+    if let Err(e) = fs::write(&output, &program.bytes) {
+        eprintln!("{}: {}", output, e);
+        process::exit(1);
+    }
+    println!("Wrote {} bytes to {}", program.bytes.len(), output);
+}
@@ -0,0 +1,16 @@
+// chip8-disasm: print a ROM as CHIPPER source
+use std::env;
+use std::fs;
+
+use chip8::disasm;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        println!("Usage: chip8-disasm [Path to rom]");
+        return;
+    }
+
+    let rom = fs::read(&args[1]).expect("File open failed");
+    print!("{}", disasm::disassemble_rom(&rom));
+}
@@ -0,0 +1,56 @@
+// chip8-headless: run a ROM without a window & print the screen, or check it against a golden image
+use std::env;
+use std::fs;
+use std::process;
+
+use chip8::cpu::Cpu;
+use chip8::headless;
+use chip8::video::MemoryVideo;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 || args.len() > 5 {
+        println!("Usage: chip8-headless [Path to rom] [Frames] [Golden image] [--write]");
+        return;
+    }
+
+    let frames = match args[2].parse() {
+        Ok(frames) => frames,
+        Err(_) => {
+            println!("Invalid frame count: {}", args[2]);
+            return;
+        }
+    };
+
+    // Fixed seed so CXNN games give the same screen every run
+    let mut cpu = Cpu::new();
+    cpu.seed_rng(0);
+    if let Err(e) = cpu.load_bin(&args[1]) {
+        println!("{}: {}", args[1], e);
+        process::exit(1);
+    }
+    let mut video = MemoryVideo::keeping(1);
+    if let Err(e) = headless::run_frames(&mut cpu, frames, &mut video) {
+        println!("{}", e);
+        process::exit(1);
+    }
+    let screen = headless::screen(video.last().unwrap_or(&Default::default()));
+
+    match (args.get(3), args.get(4).map(String::as_str)) {
+        (None, _) => print!("{}", screen),
+        (Some(golden), Some("--write")) => {
+            fs::write(golden, &screen).expect("Failed to write golden image");
+            println!("Wrote {}", golden);
+        }
+        (Some(golden), _) => {
+            let expected = fs::read_to_string(golden).expect("Failed to read golden image");
+            match headless::diff(&screen, &expected) {
+                None => println!("OK"),
+                Some(diff) => {
+                    println!("{}", diff);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// User config file, ~/.config/chip8/config.toml by default.
+// Top level settings apply to every ROM but only fill in what the ROM database leaves unset,
+// [rom."<file name or SHA-1>"] sections override them & the database for one ROM & command line flags override everything.
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::MemoryPolicy;
+use crate::database::{self, RomInfo};
+use crate::error::ErrorPolicy;
+use crate::keymap::{self, Hotkey, Keymap};
+use crate::options::Options;
+use crate::quirks::Quirks;
+
+#[derive(Default)]
+pub struct Config {
+    pub defaults: Settings,
+    pub rom: BTreeMap<String, Settings>,
+}
+
+// The config file version of the command line flags
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub preset: Option<String>,
+    pub cycles: Option<u16>,
+    pub stack: Option<StackDepth>,
+    pub memory: Option<String>,
+    pub on_error: Option<String>,
+    pub scale: Option<u32>,
+    pub palette: Option<Vec<String>>, // "#RRGGBB" background, plane 1, plane 2 & both planes
+    pub fullscreen: Option<bool>,
+    pub keymap: Option<String>,
+    pub keys: Option<BTreeMap<String, String>>, // Hex key to key name, e.g. 5 = "Up"
+    pub hotkeys: Option<BTreeMap<String, String>>, // Hotkey to key name, e.g. exit = "Escape"
+    pub mute: Option<bool>,
+    pub volume: Option<f32>,
+    pub frequency: Option<f32>,
+}
+
+// `stack = 64` or `stack = "unlimited"`
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum StackDepth {
+    Entries(u16),
+    Name(String),
+}
+
+impl Config {
+    // The [rom] sections are split off first so misspelt settings are reported at the top level too
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut table: toml::value::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+        let rom = match table.remove("rom") {
+            Some(rom) => rom.try_into().map_err(|e| format!("[rom] {}", e))?,
+            None => BTreeMap::new(),
+        };
+        let defaults = toml::Value::Table(table).try_into().map_err(|e| e.to_string())?;
+        Ok(Config { defaults, rom })
+    }
+
+    // A missing file at the default location is an empty config, a missing --config file is an error
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let (path, required) = match path {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    // Settings for a ROM, its section is looked up by file name or by the SHA-1 of the image.
+    // info is the ROM's database entry, which the top level settings don't override.
+    pub fn options(&self, rom: &str, hash: &str, info: Option<&RomInfo>) -> Result<Options, String> {
+        let name = Path::new(rom).file_name().and_then(|name| name.to_str()).unwrap_or(rom);
+        let mut defaults = self.defaults.options()?;
+        if let Some(info) = info {
+            defaults.preset = None;
+            defaults.stack_depth = None;
+            defaults.cycles = None;
+            if info.palette.is_some() {
+                defaults.palette = None;
+            }
+        }
+        match self.rom.get(name).or_else(|| self.rom.get(hash)) {
+            Some(settings) => Ok(settings.options().map_err(|e| format!("[rom.\"{}\"] {}", name, e))?.or(defaults)),
+            None => Ok(defaults),
+        }
+    }
+}
+
+impl Settings {
+    fn options(&self) -> Result<Options, String> {
+        let preset = match self.preset {
+            Some(ref name) => Some(Quirks::from_preset(name).ok_or(format!("Unknown quirks preset: {}", name))?),
+            None => None,
+        };
+        let stack_depth = match self.stack {
+            Some(StackDepth::Entries(0)) => return Err("Invalid stack depth: 0".to_string()),
+            Some(StackDepth::Entries(depth)) => Some(Some(depth)),
+            Some(StackDepth::Name(ref name)) => {
+                Some(Quirks::parse_stack_depth(name).ok_or(format!("Invalid stack depth: {}", name))?)
+            }
+            None => None,
+        };
+        let memory = match self.memory {
+            Some(ref name) => Some(MemoryPolicy::from_name(name).ok_or(format!("Unknown memory policy: {}", name))?),
+            None => None,
+        };
+        let on_error = match self.on_error {
+            Some(ref name) => Some(ErrorPolicy::from_name(name).ok_or(format!("Unknown error policy: {}", name))?),
+            None => None,
+        };
+        let palette = match self.palette {
+            Some(ref colors) => Some(database::parse_palette(colors).ok_or("Invalid palette")?),
+            None => None,
+        };
+        let keymap = match self.keymap {
+            Some(ref name) => Some(Keymap::from_preset(name).ok_or(format!("Unknown keymap: {}", name))?),
+            None => None,
+        };
+        let mut keys = BTreeMap::new();
+        for (key, name) in self.keys.iter().flatten() {
+            keys.insert(keymap::parse_key(key).ok_or(format!("Invalid keypad key: {}", key))?, name.clone());
+        }
+        let mut hotkeys = BTreeMap::new();
+        for (hotkey, name) in self.hotkeys.iter().flatten() {
+            hotkeys.insert(Hotkey::from_name(hotkey).ok_or(format!("Unknown hotkey: {}", hotkey))?, name.clone());
+        }
+        if self.scale == Some(0) {
+            return Err("scale must be at least 1".to_string());
+        }
+
+        Ok(Options {
+            preset,
+            cycles: self.cycles,
+            stack_depth,
+            memory,
+            on_error,
+            scale: self.scale,
+            palette,
+            fullscreen: self.fullscreen,
+            keymap,
+            keys,
+            hotkeys,
+            mute: self.mute,
+            volume: self.volume,
+            frequency: self.frequency,
+            ..Options::default()
+        })
+    }
+}
+
+// $XDG_CONFIG_HOME/chip8/config.toml, falling back to ~/.config & %APPDATA% on Windows
+pub fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("chip8").join("config.toml"))
+}
@@ -1,18 +1,59 @@
-use rand;
-use rand::Rng;
-use std::cmp;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
-
-use crate::display::{Display, DisplayMode, HEIGHT, WIDTH};
-use std::borrow::BorrowMut;
+use std::fs;
+use std::io;
+
+use crate::asm::{self, SourceMap};
+use crate::database::{self, Database, RomInfo};
+use crate::disasm;
+use crate::error::{Chip8Error, ErrorPolicy};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::state::{self, StateReader, StateWriter};
+use crate::video::{DisplayMode, Pixels, Video, HEIGHT, WIDTH};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Mode {
     pub debug: bool,
 }
 
+// FX0A halts execution until a key is pressed and released again (COSMAC VIP behaviour)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyWait {
+    Idle,      // Not executing FX0A
+    Waiting,   // Waiting for any key to go down
+    Held(u8),  // Key is down, waiting for it to be released
+}
+
+// Data memory read or written by the last instruction, opcode fetches are not included
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Access {
+    pub addr: usize,
+    pub len: usize,
+    pub write: bool,
+}
+
+// XO-CHIP extends the address space to 64 KiB
+pub const MEMORY_SIZE: usize = 0x10000;
+
+// Instructions per frame for ROMs the database doesn't know
+pub const DEFAULT_SPEED: u16 = 10;
+
+// What I based accesses past the end of the platform's address space (Quirks::memory_size) do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryPolicy {
+    Wrap, // Wrap around to 0x000, as the address lines do on real hardware
+    Trap, // Fail with Chip8Error::MemoryOutOfBounds
+}
+
+impl MemoryPolicy {
+    pub fn from_name(name: &str) -> Option<MemoryPolicy> {
+        match name {
+            "wrap" => Some(MemoryPolicy::Wrap),
+            "trap" => Some(MemoryPolicy::Trap),
+            _ => None,
+        }
+    }
+}
+
 // CHIP-8 Fonts
 const FONT: [u8; 240] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -42,7 +83,7 @@ const FONT: [u8; 240] = [
     0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
     0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
     0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
-    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFf, 0xC3, 0xC3, 0xC3, // A
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
     0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
     0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
     0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
@@ -52,33 +93,50 @@ const FONT: [u8; 240] = [
 
 pub struct Cpu {
     opcode: u16,
-    memory: Box<[u8; 4096]>,          // 0x000 - 0xFFF. 0x000 - 0x1FF for interpreter
+    memory: Box<[u8; MEMORY_SIZE]>,   // 0x0000 - 0xFFFF. 0x000 - 0x1FF for interpreter
     v: [u8; 16],                      // 8-bit general purpose register, (V0 - VE*).
     i: u16,                           // Index register (start at 0x200)
     pc: u16,                          // Program Counter. Jump to 0x200 on RST
-    stack: [u16; 16],                 // Interpreter returns to value when done with subroutine
-    sp: u16,                          // Stack pointer
+    stack: Vec<u16>,                  // Address of each active CALL, at most quirks.stack_depth deep
     delay_timer: u8,                  // 8-bit Delay Timer
     sound_timer: u8,                  // 8-bit Sound Timer
     snd_tick: f32,                    // Sound timer tick
     tick: f32,                        // Cpu timer tick
-    rpl_flags: [u8; 8],               // RPL User Flags (Used by opcodes FX75 & FX85)
-    pub pixels: [[bool; WIDTH]; HEIGHT],
+    rpl_flags: [u8; 16],              // RPL User Flags (Used by opcodes FX75 & FX85)
+    pub pixels: Pixels,               // Bitplane mask per pixel, bit 0 = plane 1, bit 1 = plane 2
+    pub plane: u8,                    // XO-CHIP bitplanes selected for drawing (FN01)
+    pub audio_pattern: [u8; 16],      // XO-CHIP 128 bit audio pattern buffer (F002)
+    pub pitch: u8,                    // XO-CHIP audio pattern playback pitch (FX3A)
     pub keypad: [u8; 16],             // Keypad is HEX based(0x0-0xF)
+    pub key_wait: KeyWait,            // FX0A key wait state
     pub mode: Mode,                   // Mode to turn on & off debugging
     pub display_mode: DisplayMode,    // Normal & Extended display modes
-    pub speed: u8,                    // CPU clock speed
-    draw_flag: bool,                  // Whether or not to redraw
+    pub speed: u16,                   // CPU clock speed, instructions per frame
+    pub quirks: Quirks,               // Platform specific opcode behaviour
+    pub on_error: ErrorPolicy,        // What to do when an instruction fails
+    pub memory_policy: MemoryPolicy,  // Wrap or trap I based accesses past the end of memory
+    pub rom_hash: String,             // SHA-1 of the loaded ROM
+    pub rom_info: Option<RomInfo>,    // ROM database entry for the loaded ROM
+    pub source: Option<SourceMap>,    // Source lines when running assembly source
+    pub seed: u64,                    // Seed the random number generator started from
+    rng: Rng,                         // CXNN random number generator
+    vblank: bool,                     // Set once per frame, cleared by DXYN when waiting for display
+    access: Option<Access>,           // Memory touched by the last instruction, for watchpoints
+    pub draw_flag: bool,              // Whether or not to redraw
                                       // *VF is a special register used to store overflow bit
 }
 
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Cpu {
     pub fn new() -> Cpu {
-        let mut memory = [0; 4096];
-
-        for i in 0..240 {
-            memory[i] = FONT[i];
-        }
+        let mut memory = [0; MEMORY_SIZE];
+        memory[..FONT.len()].copy_from_slice(&FONT);
+        let seed = Rng::time_seed();
 
         Cpu {
             opcode: 0,
@@ -86,40 +144,105 @@ impl Cpu {
             v: [0; 16],
             i: 0,
             pc: 0x200,
-            stack: [0; 16],
-            sp: 0,
+            stack: Vec::new(),
             delay_timer: 0,
             sound_timer: 0,
             snd_tick: 0.0,
             tick: 0.0,
-            rpl_flags: [0; 8],
-            pixels: [[false; WIDTH]; HEIGHT],
+            rpl_flags: [0; 16],
+            pixels: [[0; WIDTH]; HEIGHT],
+            plane: 1,
+            audio_pattern: [0; 16],
+            pitch: 64,
             keypad: [0; 16],
+            key_wait: KeyWait::Idle,
             mode: Mode { debug: false },
             display_mode: DisplayMode::Normal,
-            speed: 2,
+            speed: DEFAULT_SPEED,
+            quirks: Quirks::default(),
+            on_error: ErrorPolicy::Halt,
+            memory_policy: MemoryPolicy::Wrap,
+            rom_hash: String::new(),
+            rom_info: None,
+            source: None,
+            seed,
+            rng: Rng::new(seed),
+            vblank: true,
+            access: None,
             draw_flag: false,
         }
     }
 
-    pub fn load_bin(&mut self, file: &str) {
-        let path = Path::new(file);
-        let mut file = File::open(&path).expect("File open failed");
-        let mut buf = Vec::new();
+    pub fn load_bin(&mut self, file: &str) -> Result<(), Chip8Error> {
+        let buf = fs::read(file)?;
+        self.load_known_rom(&buf)
+    }
+
+    // Assemble CHIPPER (.src) or Octo (.8o) source in memory & load it
+    pub fn load_source(&mut self, file: &str) -> Result<(), Chip8Error> {
+        let text = fs::read_to_string(file)?;
+        let program = asm::assemble(file, &text)?;
 
-        file.read_to_end(&mut buf).expect("Failed to read file");
+        println!("Assembled {} bytes from {}", program.bytes.len(), file);
+        self.load_known_rom(&program.bytes)?;
+        self.source = Some(SourceMap::new(file, &program));
+        Ok(())
+    }
+
+    // Load a ROM & pick platform quirks & speed for it from the database. The database platform decides how large
+    // the ROM may be, but its settings only apply once the ROM has loaded.
+    fn load_known_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let info = Database::bundled().lookup(&database::sha1(rom)).cloned();
+        let quirks = info.as_ref().map_or(self.quirks, |info| info.quirks);
+        Self::check_rom_size(rom, &quirks)?;
+        self.copy_rom(rom);
+
+        match info {
+            Some(info) => {
+                println!("{} ({}), speed {}", info.title, info.platform, info.tickrate);
+                self.quirks = info.quirks;
+                self.speed = info.tickrate;
+                self.rom_info = Some(info);
+            }
+            // Don't keep the previous ROM's database settings
+            None => {
+                if self.rom_info.take().is_some() {
+                    self.quirks = Quirks::default();
+                    self.speed = DEFAULT_SPEED;
+                }
+            }
+        }
+        Ok(())
+    }
 
-        if buf.len() >= 3585 {
-            panic!("ROM is too large, size: {}", buf.len());
+    // A ROM has to fit the platform's address space (Quirks::memory_size) from 0x200 up
+    fn check_rom_size(rom: &[u8], quirks: &Quirks) -> Result<(), Chip8Error> {
+        let max = quirks.memory_size.min(MEMORY_SIZE) - 0x200;
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max });
         }
+        Ok(())
+    }
+
+    // Copy a ROM image into memory at 0x200
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        Self::check_rom_size(rom, &self.quirks)?;
+        self.copy_rom(rom);
+        Ok(())
+    }
 
-        let buf_len = buf.len();
-        for i in 0..buf_len {
-            self.memory[i + 512] = buf[i];
+    fn copy_rom(&mut self, rom: &[u8]) {
+        // Clear anything left over from a previous ROM
+        for byte in self.memory[0x200..].iter_mut() {
+            *byte = 0;
         }
+        self.memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+        self.rom_hash = database::sha1(rom);
     }
 
     pub fn update_timers(&mut self, dt: f32) {
+        self.vblank = true;
+
         if self.delay_timer > 0 {
             self.tick -= dt;
 
@@ -142,6 +265,99 @@ impl Cpu {
         }
     }
 
+    // The buzzer sounds for as long as the sound timer is non-zero
+    pub fn beeping(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    // XO-CHIP audio pattern playback rate in bits per second: 4000 * 2 ^ ((pitch - 64) / 48)
+    pub fn pattern_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((f32::from(self.pitch) - 64.0) / 48.0)
+    }
+
+    // Read only views of the machine for the debugger
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.stack.len() as u16
+    }
+
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..]
+    }
+
+    // Set up machine state directly, for tests & tools
+    pub fn set_v(&mut self, x: usize, value: u8) {
+        self.v[x] = value;
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn write_memory(&mut self, addr: usize, data: &[u8]) {
+        self.memory[addr..addr + data.len()].copy_from_slice(data);
+    }
+
+    pub fn set_key(&mut self, key: usize, down: bool) {
+        self.keypad[key] = down as u8;
+    }
+
+    // Place opcode at PC & execute it as the next instruction
+    pub fn execute(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let pc = self.pc as usize;
+        self.write_memory(pc, &opcode.to_be_bytes());
+        self.run()
+    }
+
+    // Source location of addr when running assembly source, e.g. BRIX.SRC:123 (#0234)
+    pub fn location(&self, addr: u16) -> String {
+        match self.source.as_ref().and_then(|s| s.line(addr).map(|line| (&s.file, line))) {
+            Some((file, line)) => format!("{}:{} (#{:04X})", file, line, addr),
+            None => format!("#{:04X}", addr),
+        }
+    }
+
+    pub fn last_access(&self) -> Option<Access> {
+        self.access
+    }
+
+    fn touch(&mut self, addr: usize, len: usize, write: bool) {
+        self.access = Some(Access { addr, len, write });
+    }
+
+    // Restart the random number generator from a known seed so runs can be reproduced
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
     pub fn reset(&mut self) {
         println!("Reset interpreter");
 
@@ -151,120 +367,344 @@ impl Cpu {
 
         self.i = 0;
         self.pc = 0x200;
-        self.sp = 0;
+        self.stack.clear();
 
         self.delay_timer = 0;
         self.sound_timer = 0;
+        self.key_wait = KeyWait::Idle;
+        self.rng = Rng::new(self.seed);
 
-        self.pixels = [[false; WIDTH]; HEIGHT];
+        self.pixels = [[0; WIDTH]; HEIGHT];
+        self.plane = 1;
+        self.audio_pattern = [0; 16];
+        self.pitch = 64;
         self.draw_flag = true;
     }
 
+    // Serialize the full machine state, see state.rs for the file layout
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+
+        w.bytes(self.rom_hash.as_bytes());
+        w.u8(b'\n');
+        w.bytes(&self.memory[..]);
+        w.bytes(&self.v);
+        w.u16(self.i);
+        w.u16(self.pc);
+        w.u16(self.stack.len() as u16);
+        for &addr in &self.stack {
+            w.u16(addr);
+        }
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        w.f32(self.snd_tick);
+        w.f32(self.tick);
+        w.bytes(&self.rpl_flags);
+        for row in self.pixels.iter() {
+            w.bytes(row);
+        }
+        w.u8(self.plane);
+        w.bytes(&self.audio_pattern);
+        w.u8(self.pitch);
+        match self.key_wait {
+            KeyWait::Idle => w.bytes(&[0, 0]),
+            KeyWait::Waiting => w.bytes(&[1, 0]),
+            KeyWait::Held(key) => w.bytes(&[2, key]),
+        }
+        w.bool(self.display_mode == DisplayMode::Extended);
+        w.bool(self.quirks.shift);
+        w.bool(self.quirks.load_store);
+        w.bool(self.quirks.jump);
+        w.bool(self.quirks.clip);
+        w.bool(self.quirks.vf_reset);
+        w.bool(self.quirks.display_wait);
+        w.bool(self.quirks.half_scroll);
+        w.u16(self.quirks.stack_depth.unwrap_or(0));
+        w.u64(self.quirks.memory_size as u64);
+        w.u16(self.speed);
+        w.bool(self.vblank);
+        w.u64(self.seed);
+        w.u64(self.rng.state());
+
+        w.finish()
+    }
+
+    // Restore a state written by save_state. Nothing is changed if the state is invalid.
+    pub fn load_state(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut r = StateReader::new(data)?;
+        let mut cpu = Cpu::new();
+
+        let mut hash = Vec::new();
+        loop {
+            match r.u8()? {
+                b'\n' => break,
+                c => hash.push(c),
+            }
+        }
+        cpu.rom_hash = String::from_utf8(hash).map_err(|_| state::invalid("Bad ROM hash"))?;
+        if cpu.rom_hash != self.rom_hash {
+            println!("Warning: save state was made with a different ROM");
+        }
+
+        r.bytes(&mut cpu.memory[..])?;
+        r.bytes(&mut cpu.v)?;
+        cpu.i = r.u16()?;
+        cpu.pc = r.u16()?;
+        for _ in 0..r.u16()? {
+            cpu.stack.push(r.u16()?);
+        }
+        cpu.delay_timer = r.u8()?;
+        cpu.sound_timer = r.u8()?;
+        cpu.snd_tick = r.f32()?;
+        cpu.tick = r.f32()?;
+        r.bytes(&mut cpu.rpl_flags)?;
+        for row in cpu.pixels.iter_mut() {
+            r.bytes(row)?;
+        }
+        cpu.plane = r.u8()?;
+        r.bytes(&mut cpu.audio_pattern)?;
+        cpu.pitch = r.u8()?;
+        cpu.key_wait = match (r.u8()?, r.u8()?) {
+            (0, _) => KeyWait::Idle,
+            (1, _) => KeyWait::Waiting,
+            (2, key) if key <= 0xF => KeyWait::Held(key),
+            _ => return Err(state::invalid("Bad key wait state")),
+        };
+        cpu.display_mode = if r.bool()? {
+            DisplayMode::Extended
+        } else {
+            DisplayMode::Normal
+        };
+        cpu.quirks.shift = r.bool()?;
+        cpu.quirks.load_store = r.bool()?;
+        cpu.quirks.jump = r.bool()?;
+        cpu.quirks.clip = r.bool()?;
+        cpu.quirks.vf_reset = r.bool()?;
+        cpu.quirks.display_wait = r.bool()?;
+        cpu.quirks.half_scroll = r.bool()?;
+        cpu.quirks.stack_depth = Some(r.u16()?).filter(|&depth| depth > 0);
+        cpu.quirks.memory_size = state::memory_size(r.u64()?)?;
+        cpu.speed = r.u16()?;
+        cpu.vblank = r.bool()?;
+        cpu.seed = r.u64()?;
+        cpu.rng = Rng::from_state(r.u64()?);
+
+        // Front end settings aren't part of the machine state
+        cpu.mode = self.mode;
+        cpu.on_error = self.on_error;
+        cpu.memory_policy = self.memory_policy;
+        cpu.rom_info = self.rom_info.take();
+        cpu.source = self.source.take();
+        cpu.keypad = self.keypad;
+        cpu.draw_flag = true;
+
+        *self = cpu;
+        Ok(())
+    }
+
+    // Skip the next instruction, stepping over both words of an XO-CHIP F000 NNNN
+    fn skip_next(&mut self) -> Result<(), Chip8Error> {
+        let next = self.word(self.wrap(self.pc as usize + 2))?;
+
+        self.advance(if next == 0xF000 { 6 } else { 4 });
+        Ok(())
+    }
+
+    // Move PC on, wrapping around the address space like I
+    fn advance(&mut self, bytes: u16) {
+        self.pc = self.wrap(self.pc as usize + bytes as usize) as u16;
+    }
+
+    // Big endian word at addr
+    fn word(&self, addr: usize) -> Result<u16, Chip8Error> {
+        self.check_bounds(addr, 2)?;
+        Ok((self.memory[addr] as u16) << 8 | (self.memory[addr + 1] as u16))
+    }
+
+    // Error unless addr..addr + len is inside memory
+    fn check_bounds(&self, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if addr + len > MEMORY_SIZE {
+            return Err(Chip8Error::MemoryOutOfBounds { pc: self.pc, addr: addr.max(MEMORY_SIZE) });
+        }
+        Ok(())
+    }
+
+    // I based addresses wrap around the platform's address space, 4 KiB on the VIP & 64 KiB on XO-CHIP
+    fn wrap(&self, addr: usize) -> usize {
+        addr & (self.quirks.memory_size - 1)
+    }
+
+    // With the Trap memory policy I based accesses past the end of the address space fail
+    fn check_access(&self, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        let size = self.quirks.memory_size;
+        if self.memory_policy == MemoryPolicy::Trap && addr + len > size {
+            return Err(Chip8Error::MemoryOutOfBounds { pc: self.pc, addr: addr.max(size) });
+        }
+        Ok(())
+    }
+
+    fn peek(&self, addr: usize) -> u8 {
+        self.memory[self.wrap(addr)]
+    }
+
+    fn poke(&mut self, addr: usize, value: u8) {
+        let addr = self.wrap(addr);
+        self.memory[addr] = value;
+    }
+
+    // Value of I pointing at addr, kept inside the address space when wrapping.
+    // Trap leaves I past the end for the next access to fail, unless it doesn't fit in I at all.
+    fn index(&self, addr: usize) -> Result<u16, Chip8Error> {
+        match self.memory_policy {
+            MemoryPolicy::Wrap => Ok(self.wrap(addr) as u16),
+            MemoryPolicy::Trap if addr > 0xFFFF => Err(Chip8Error::MemoryOutOfBounds { pc: self.pc, addr }),
+            MemoryPolicy::Trap => Ok(addr as u16),
+        }
+    }
+
+    // SCHIP scrolls by hires pixels, so half as many lores pixels, XO-CHIP always scrolls n pixels
+    fn scroll_distance(&self, n: usize) -> usize {
+        if self.display_mode == DisplayMode::Normal && self.quirks.half_scroll {
+            n / 2
+        } else {
+            n
+        }
+    }
+
+    fn unknown_opcode(&self) -> Chip8Error {
+        Chip8Error::UnknownOpcode { pc: self.pc, opcode: self.opcode }
+    }
+
+    // Execute one instruction, with the Skip policy failing instructions are stepped over
+    pub fn run(&mut self) -> Result<(), Chip8Error> {
+        match self.cycle() {
+            Err(e) if self.on_error == ErrorPolicy::Skip => {
+                println!("{}, skipped", e);
+                self.advance(2);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
     // Fetch high & low bytes & merge
-    pub fn run(&mut self, display: &mut Display) {
-        self.opcode = (self.memory[self.pc as usize] as u16) << 8
-            | (self.memory[self.pc as usize + 1] as u16);
+    fn cycle(&mut self) -> Result<(), Chip8Error> {
+        self.access = None;
+        self.opcode = self.word(self.pc as usize)?;
 
         // Decode Vx & Vy register identifiers.
         let x = ((self.opcode & 0x0F00) >> 8) as usize;
         let y = ((self.opcode & 0x00F0) >> 4) as usize;
 
-        let nnn: u16 = (self.opcode & 0x0FFF) as u16; // addr 12-bit value
+        let nnn: u16 = self.opcode & 0x0FFF; // addr 12-bit value
         let kk: u8 = (self.opcode & 0x00FF) as u8; // u8, byte 8-bit value
 
-        if self.mode.debug == true {
-            println!(
-                "Opcode: {:X} | PC: {:#?} | SP: {:X} | I: {:X} | V0: {} | \
-                 V1: {} | V2: {} | V3: {} | V4: {} | V5: {} | \
-                 V6: {} | V7: {} | V8: {} | V9: {} | VA: {} | \
-                 VB: {} | VC: {} | VD: {} | VE: {} | VF: {}",
-                self.opcode,
-                self.pc,
-                self.sp,
-                self.i,
-                self.v[0],
-                self.v[1],
-                self.v[2],
-                self.v[3],
-                self.v[4],
-                self.v[5],
-                self.v[6],
-                self.v[7],
-                self.v[8],
-                self.v[9],
-                self.v[10],
-                self.v[11],
-                self.v[12],
-                self.v[13],
-                self.v[14],
-                self.v[15]
-            );
+        // Instruction trace, toggled from the debugger
+        if self.mode.debug {
+            let text = disasm::disassemble(&self.memory[..], self.pc as usize);
+            match self.source {
+                Some(_) => println!("{:04X}: {:04X}  {:<24}{}", self.pc, self.opcode, text, self.location(self.pc)),
+                None => println!("{:04X}: {:04X}  {}", self.pc, self.opcode, text),
+            }
         }
 
         // Relying on the first 4 bits is not enough in this case.
         // We need to compare the last four bits, hence the second match block.
         match self.opcode & 0xF000 {
-            0x0000 => {
+            // 0NNN machine code calls aren't supported, only the 00NN instructions
+            0x0000 if x == 0 => {
                 match self.opcode & 0x00F0 {
                     // 00CN SCHIP Scroll down N lines
                     0x00C0 => {
                         let n = (self.opcode & 0x000F) as usize;
-                        let extended = self.display_mode == DisplayMode::Extended;
-                        let i = if extended { n } else { n / 2 };
+                        let i = self.scroll_distance(n);
+                        let plane = self.plane;
 
                         // Subtract n from y to scroll down n height
                         for x in 0..WIDTH {
                             for y in (i..HEIGHT).rev() {
-                                self.pixels[y][x] = self.pixels[y - i][x];
+                                let src = self.pixels[y - i][x] & plane;
+                                self.pixels[y][x] = (self.pixels[y][x] & !plane) | src;
                             }
                             for y in 0..i {
-                                self.pixels[y][x] = false;
+                                self.pixels[y][x] &= !plane;
                             }
                         }
 
-                        display.draw(&self.pixels, 10, 10);
                         self.draw_flag = true;
 
-                        self.pc += 2;
+                        self.advance(2);
                         if self.mode.debug {
                             println!("Scroll down N lines");
                         }
                     }
 
+                    // 00DN XO-CHIP Scroll up N lines
+                    0x00D0 => {
+                        let n = (self.opcode & 0x000F) as usize;
+                        let i = self.scroll_distance(n);
+                        let plane = self.plane;
+
+                        // Add n to y to scroll up n height
+                        for x in 0..WIDTH {
+                            for y in 0..(HEIGHT - i) {
+                                let src = self.pixels[y + i][x] & plane;
+                                self.pixels[y][x] = (self.pixels[y][x] & !plane) | src;
+                            }
+                            for y in (HEIGHT - i)..HEIGHT {
+                                self.pixels[y][x] &= !plane;
+                            }
+                        }
+
+                        self.draw_flag = true;
+
+                        self.advance(2);
+                        if self.mode.debug {
+                            println!("Scroll up N lines");
+                        }
+                    }
+
                     _ => match self.opcode & 0x00FF {
                         // 00E0 (CLS) Clear screen
+                        // XO-CHIP: only the selected bitplanes are cleared
                         0x00E0 => {
-                            self.pixels = [[false; WIDTH]; HEIGHT];
+                            for row in self.pixels.iter_mut() {
+                                for pixel in row.iter_mut() {
+                                    *pixel &= !self.plane;
+                                }
+                            }
                             self.draw_flag = true;
-                            self.pc += 2;
+                            self.advance(2);
                         }
 
                         // 00EE (RET) Return from subroutine call
                         0x00EE => {
-                            self.sp -= 1;
-                            self.pc = self.stack[(self.sp as usize)];
-                            self.pc += 2;
+                            match self.stack.pop() {
+                                Some(addr) => {
+                                    self.pc = addr;
+                                    self.advance(2);
+                                }
+                                None => return Err(Chip8Error::StackUnderflow { pc: self.pc }),
+                            }
                         }
 
                         // 00FB (SCHIP) Scroll screen 4 pixels right
                         0x00FB => {
-                            let extended = self.display_mode == DisplayMode::Extended;
-                            let i = if extended { 4 } else { 2 };
+                            let i = self.scroll_distance(4);
+                            let plane = self.plane;
 
-                            for y in 0..HEIGHT {
+                            for row in self.pixels.iter_mut() {
                                 for x in (i..WIDTH).rev() {
-                                    self.pixels[y][x] = self.pixels[y][x - i];
+                                    row[x] = (row[x] & !plane) | (row[x - i] & plane);
                                 }
 
-                                for x in 0..i {
-                                    self.pixels[y][x] = false;
+                                for pixel in row.iter_mut().take(i) {
+                                    *pixel &= !plane;
                                 }
                             }
 
-                            display.draw(&self.pixels, 10, 10);
-
                             self.draw_flag = true;
-                            self.pc += 2;
+                            self.advance(2);
 
                             if self.mode.debug {
                                 println!("Scroll 4 pixels right");
@@ -273,23 +713,21 @@ impl Cpu {
 
                         // 00FC (SCHIP) Scroll screen 4 pixels left
                         0x00FC => {
-                            let extended = self.display_mode == DisplayMode::Extended;
-                            let i = if extended { 4 } else { 2 };
+                            let i = self.scroll_distance(4);
+                            let plane = self.plane;
 
-                            for y in 0..HEIGHT {
+                            for row in self.pixels.iter_mut() {
                                 for x in 0..(WIDTH - i) {
-                                    self.pixels[y][x] = self.pixels[y][x + i];
+                                    row[x] = (row[x] & !plane) | (row[x + i] & plane);
                                 }
 
-                                for x in (WIDTH - i)..WIDTH {
-                                    self.pixels[y][x] = false;
+                                for pixel in row.iter_mut().skip(WIDTH - i) {
+                                    *pixel &= !plane;
                                 }
                             }
 
-                            display.draw(&self.pixels, 10, 10);
-
                             self.draw_flag = true;
-                            self.pc += 2;
+                            self.advance(2);
 
                             if self.mode.debug {
                                 println!("Scroll 4 pixels left");
@@ -299,7 +737,7 @@ impl Cpu {
                         // 00FE (SCHIP) Disable extended screen mode
                         0x00FE => {
                             self.display_mode = DisplayMode::Normal;
-                            self.pc += 2;
+                            self.advance(2);
 
                             if self.mode.debug {
                                 println!("(Extended mode disabled");
@@ -319,13 +757,10 @@ impl Cpu {
                                 println!("Extended mode enabled");
                             }
 
-                            self.pc += 2;
+                            self.advance(2);
                         }
 
-                        0x0000 => {
-                            return;
-                        }
-                        _ => println!("Unknown opcode: 00{:X}", self.opcode),
+                        _ => return Err(self.unknown_opcode()),
                     },
                 }
             }
@@ -337,51 +772,78 @@ impl Cpu {
 
             // 2NNN Call suboutine at address nnn
             0x2000 => {
-                self.stack[self.sp as usize] = self.pc;
-
-                // Stack size is 16 so we need to wrap
-                self.sp = self.sp.wrapping_add(1);
+                if self.quirks.stack_depth.is_some_and(|depth| self.stack.len() >= depth as usize) {
+                    return Err(Chip8Error::StackOverflow { pc: self.pc, calls: self.stack.clone() });
+                }
+                self.stack.push(self.pc);
                 self.pc = nnn;
             }
 
             // 3XKK Skip next instruction if Vx = kk
             0x3000 => {
                 if self.v[x] == kk {
-                    self.pc += 4;
+                    self.skip_next()?;
                 } else {
-                    self.pc += 2;
+                    self.advance(2);
                 }
             }
 
             // 4XKK Skip next instruction if Vx != kk
             0x4000 => {
-                if self.v[x as usize] != kk {
-                    self.pc += 4;
+                if self.v[x] != kk {
+                    self.skip_next()?;
                 } else {
-                    self.pc += 2;
+                    self.advance(2);
                 }
             }
 
-            // 5XY0 Skip next instruction if Vx = Vy
             0x5000 => {
-                if self.v[x] == self.v[y] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
+                match self.opcode & 0x000F {
+                    // 5XY0 Skip next instruction if Vx = Vy
+                    0x0000 => {
+                        if self.v[x] == self.v[y] {
+                            self.skip_next()?;
+                        } else {
+                            self.advance(2);
+                        }
+                    }
+
+                    // 5XY2 XO-CHIP Store Vx to Vy in memory starting at I, I is not changed
+                    0x0002 => {
+                        let i = self.i as usize;
+                        self.check_access(i, x.max(y) - x.min(y) + 1)?;
+                        for (offset, reg) in Self::register_range(x, y).enumerate() {
+                            self.poke(i + offset, self.v[reg]);
+                        }
+                        self.touch(i, x.max(y) - x.min(y) + 1, true);
+                        self.advance(2);
+                    }
+
+                    // 5XY3 XO-CHIP Load Vx to Vy from memory starting at I, I is not changed
+                    0x0003 => {
+                        let i = self.i as usize;
+                        self.check_access(i, x.max(y) - x.min(y) + 1)?;
+                        for (offset, reg) in Self::register_range(x, y).enumerate() {
+                            self.v[reg] = self.peek(i + offset);
+                        }
+                        self.touch(i, x.max(y) - x.min(y) + 1, false);
+                        self.advance(2);
+                    }
+                    _ => return Err(self.unknown_opcode()),
                 }
             }
 
             // 6XKK Set Vx = kk. Put value of kk in to Vx register
             0x6000 => {
                 self.v[x] = kk;
-                self.pc += 2;
+                self.advance(2);
             }
 
             // 7XKK Add value kk to Vx
             0x7000 => {
                 // Wrapping addition, prevents add overflow
                 self.v[x] = self.v[x].wrapping_add(kk);
-                self.pc += 2;
+                self.advance(2);
             }
 
             // 8XY0 Set Vx = Vy
@@ -389,25 +851,34 @@ impl Cpu {
                 match self.opcode & 0x000F {
                     0x0000 => {
                         self.v[x] = self.v[y];
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // 8XY1 Set Vx to Vx OR Vy
                     0x0001 => {
                         self.v[x] |= self.v[y];
-                        self.pc += 2;
+                        if self.quirks.vf_reset {
+                            self.v[0xF] = 0;
+                        }
+                        self.advance(2);
                     }
 
                     // 8XY2 Set Vx to Vx AND Vy
                     0x0002 => {
                         self.v[x] &= self.v[y];
-                        self.pc += 2;
+                        if self.quirks.vf_reset {
+                            self.v[0xF] = 0;
+                        }
+                        self.advance(2);
                     }
 
                     // 8XY3 Set Vx to Vx XOR Vy
                     0x0003 => {
                         self.v[x] ^= self.v[y];
-                        self.pc += 2;
+                        if self.quirks.vf_reset {
+                            self.v[0xF] = 0;
+                        }
+                        self.advance(2);
                     }
 
                     // 8XY4 Set Vx = Vx + Vy, set VF = carry
@@ -417,7 +888,7 @@ impl Cpu {
 
                         // Set carry if the value of Vx + Vy is larger than 0xFF (255)
                         self.v[0xF] = (reg > 0xFF) as u8;
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // 8XY5 Set Vx = Vx - Vy, set VF = NOT borrow v[0xF]
@@ -427,16 +898,18 @@ impl Cpu {
                         self.v[x] = self.v[x].wrapping_sub(self.v[y]);
                         self.v[0xF] = if borrow { 0 } else { 1 };
 
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // 8XY6 Vx = Vx Shift right by 1 If the least-significant bit of
                     // Vx is 1 then VF is set to 1, otherwise 0.
+                    // Without the shift quirk Vy is shifted into Vx (COSMAC VIP).
                     0x0006 => {
-                        self.v[0xF] = self.v[x] & 1;
-                        self.v[x] >>= 1;
+                        let reg = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                        self.v[x] = reg >> 1;
+                        self.v[0xF] = reg & 1;
 
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // 8XY7 Set Vx = Vy - Vx, VF NOT borrow
@@ -446,55 +919,67 @@ impl Cpu {
                         self.v[x] = self.v[y].wrapping_sub(self.v[x]);
                         self.v[0xF] = if borrow { 0 } else { 1 };
 
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // 8XYE
                     // If the most-significant bit of Vx is 1 then VF is set to 1
                     // Otherwise VF is set to 0 and Vx is multiplied by 2.
+                    // Without the shift quirk Vy is shifted into Vx (COSMAC VIP).
                     0x000E => {
-                        self.v[0xF] = (self.v[x] >> 7) & 1;
-                        self.v[x] <<= 1;
-                        self.pc += 2;
+                        let reg = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                        self.v[x] = reg << 1;
+                        self.v[0xF] = (reg >> 7) & 1;
+                        self.advance(2);
                     }
-                    _ => println!("Unknown opcode [0x8000], {:X}", self.opcode),
+                    _ => return Err(self.unknown_opcode()),
                 }
             }
 
             // 9XY0 Skip next instruction if Vx != Vy
-            0x9000 => {
+            0x9000 if self.opcode & 0x000F == 0 => {
                 if self.v[x] != self.v[y] {
-                    self.pc += 4;
+                    self.skip_next()?;
                 } else {
-                    self.pc += 2;
+                    self.advance(2);
                 }
             }
             // ANNN Load index register (I) with NNN
             0xA000 => {
                 self.i = nnn;
-                self.pc += 2;
+                self.advance(2);
             }
             // BNNN Jump to address NNN + V0
+            // With the jump quirk BXNN jumps to XNN + Vx (CHIP-48 & SCHIP)
             0xB000 => {
-                self.pc = nnn.wrapping_add(self.v[0] as u16);
+                let reg = if self.quirks.jump { self.v[x] } else { self.v[0] };
+                self.pc = nnn.wrapping_add(reg as u16);
             }
 
             // CXNN Set Vx to a random number masked by kk
             0xC000 => {
-                self.v[x] = rand::thread_rng().gen_range(0, 255) & kk;
-                self.pc += 2;
+                self.v[x] = self.rng.next_u8() & kk;
+                self.advance(2);
             }
 
             // DXYN Draw sprite starting at x, y, n specifies how many bytes the sprite is
             // Starting at memory location of self.i
             0xD000 => {
+                // Hold on this instruction until the next frame when waiting for the vertical blank
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        return Ok(());
+                    }
+                    self.vblank = false;
+                }
+
                 let n: bool = match self.opcode & 0x000F {
                     // Draw extended or not
                     0x0000 => true,
                     _ => false,
                 };
-                self.draw((*display).borrow_mut(), n);
-                self.pc += 2;
+                self.draw(n)?;
+                self.advance(2);
             }
 
             0xE000 => {
@@ -502,81 +987,118 @@ impl Cpu {
                     // EX9E Skip next instruction if key stored in Vx is pressed
                     // Usually the next instruction is JMP to skip to a code block
                     0x009E => {
-                        if self.keypad[self.v[x] as usize] != 0 {
-                            self.pc += 4;
+                        if self.keypad[self.v[x] as usize & 0xF] != 0 {
+                            self.skip_next()?;
                         } else {
-                            self.pc += 2;
+                            self.advance(2);
                         }
                     }
 
                     // EXA1 Skip next instruction if key stored in Vx isn't pressed
                     0x00A1 => {
-                        if self.keypad[self.v[x] as usize] != 1 {
-                            self.pc += 4;
+                        if self.keypad[self.v[x] as usize & 0xF] != 1 {
+                            self.skip_next()?;
                         } else {
-                            self.pc += 2;
+                            self.advance(2);
                         }
                     }
-                    _ => println!("Unknown opcode: 0xE000 {:02X}", self.opcode),
+                    _ => return Err(self.unknown_opcode()),
                 }
             }
 
             0xF000 => {
                 match self.opcode & 0x00FF {
+                    // F000 NNNN XO-CHIP Load I with the 16-bit address in the next word
+                    0x0000 if x == 0 => {
+                        let addr = self.word(self.pc as usize + 2)?;
+                        self.i = self.index(addr as usize)?;
+                        self.advance(4);
+                    }
+
+                    // FN01 XO-CHIP Select bitplanes N for drawing, clearing & scrolling
+                    0x0001 => {
+                        self.plane = x as u8 & 0x3;
+                        self.advance(2);
+                    }
+
+                    // F002 XO-CHIP Load the 16 byte audio pattern buffer from I
+                    0x0002 if x == 0 => {
+                        let i = self.i as usize;
+                        self.check_access(i, 16)?;
+                        for n in 0..16 {
+                            self.audio_pattern[n] = self.peek(i + n);
+                        }
+                        self.touch(i, 16, false);
+                        self.advance(2);
+                    }
+
                     // FX07 Set delay timer to Vx
                     0x0007 => {
                         self.v[x] = self.delay_timer;
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // FX0A Key press awaited then stored in Vx
-                    // All instructions halted until next key event
-                    // Iterate through all possibilities up to 0xF
-                    0x000A => {
-                        for i in 0..0xF {
-                            if self.keypad[i] != 0 {
-                                self.v[x] = i as u8;
-                                break;
+                    // All instructions halted until a key is pressed & released,
+                    // pc stays on FX0A so timers keep running while we wait.
+                    0x000A => match self.key_wait {
+                        KeyWait::Idle | KeyWait::Waiting => {
+                            self.key_wait = match self.keypad.iter().position(|&k| k != 0) {
+                                Some(key) => KeyWait::Held(key as u8),
+                                None => KeyWait::Waiting,
+                            };
+                        }
+                        KeyWait::Held(key) => {
+                            if self.keypad[key as usize] == 0 {
+                                self.v[x] = key;
+                                self.key_wait = KeyWait::Idle;
+                                self.advance(2);
                             }
                         }
-                        self.pc += 2;
-                    }
+                    },
 
                     // FX15 Set delay timer
                     0x0015 => {
                         self.delay_timer = self.v[x];
                         self.tick = 1.0 / 60.0;
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // FX18 Set sound timer
                     0x0018 => {
                         self.sound_timer = self.v[x];
                         self.snd_tick = 1.0 / 60.0;
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
-                    // FX1E Add Vx to I (MEM) VF is set to 1 when range overflow (I +VX> 0xFFF)
+                    // FX1E Add Vx to I (MEM) VF is set to 1 when I + Vx runs past the end of memory
                     0x001E => {
-                        self.i += self.v[x] as u16;
-                        self.v[0xF] = (self.i > 0xFFF) as u8;
-                        self.pc += 2;
+                        let addr = self.i as usize + self.v[x] as usize;
+                        self.i = self.index(addr)?;
+                        self.v[0xF] = (addr >= self.quirks.memory_size) as u8;
+                        self.advance(2);
                     }
 
                     // FX29 Set I to the location of the sprite (5 byte) for char in Vx
                     // Chars 0-F are represented by a 4x5 font Each char contains 5 elements
                     // Create 0x5 font accessible in memory
                     0x0029 => {
-                        self.i = (self.v[x] * 0x5) as u16;
+                        self.i = u16::from(self.v[x]) * 5;
                         // if self.mode.debug { println!("At FX29. Vx: {}, I:{}", self.v[x], self.i); }
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // FX30 SCHIP Set I to the location of the sprite (10 byte) for digit in VX
                     0x0030 => {
-                        self.i = (self.v[x] * 10 + 80) as u16;
+                        self.i = u16::from(self.v[x]) * 10 + 80;
                         // if self.mode.debug { println!("At FX30. Vx: {}, I:{}", self.v[x], self.i); }
-                        self.pc += 2;
+                        self.advance(2);
+                    }
+
+                    // FX3A XO-CHIP Set audio pattern pitch to Vx
+                    0x003A => {
+                        self.pitch = self.v[x];
+                        self.advance(2);
                     }
 
                     // FX33 (BCD) The interpreter takes the decimal value of Vx
@@ -584,105 +1106,154 @@ impl Cpu {
                     // the tens digit at location I+1, and the ones digit at location I+2.
                     0x0033 => {
                         let i = self.i as usize;
+                        self.check_access(i, 3)?;
 
-                        self.memory[i] = self.v[x] / 100;
-                        self.memory[i + 1] = self.v[x] % 100 / 10;
-                        self.memory[i + 2] = self.v[x] % 10;
+                        self.poke(i, self.v[x] / 100);
+                        self.poke(i + 1, self.v[x] % 100 / 10);
+                        self.poke(i + 2, self.v[x] % 10);
+                        self.touch(i, 3, true);
 
                         if self.mode.debug {
                             println!(
                                 " BCD: Hundreds:{}, Tens:{}, Ones:{}",
-                                self.memory[i],
-                                self.memory[i + 1],
-                                self.memory[i + 2]
+                                self.peek(i),
+                                self.peek(i + 1),
+                                self.peek(i + 2)
                             );
                         }
-                        self.pc += 2;
+                        self.advance(2);
                     }
 
                     // FX55 Stores V0 to VX in memory starting at I
+                    // With the load/store quirk I is left at I + X + 1 (COSMAC VIP)
                     0x0055 => {
+                        let i = self.i as usize;
+                        self.check_access(i, x + 1)?;
+                        let next = if self.quirks.load_store { self.index(i + x + 1)? } else { self.i };
                         for index in 0..(x + 1) {
-                            self.memory[index + self.i as usize] = self.v[index];
+                            self.poke(i + index, self.v[index]);
                         }
-                        self.pc += 2;
+                        self.touch(i, x + 1, true);
+                        self.i = next;
+                        self.advance(2);
                     }
 
                     // FX65 Fills V0 to VX with values from memory starting at I
+                    // With the load/store quirk I is left at I + X + 1 (COSMAC VIP)
                     0x0065 => {
+                        let i = self.i as usize;
+                        self.check_access(i, x + 1)?;
+                        let next = if self.quirks.load_store { self.index(i + x + 1)? } else { self.i };
                         for index in 0..(x + 1) {
-                            self.v[index] = self.memory[(self.i as usize + index)];
+                            self.v[index] = self.peek(i + index);
                         }
-                        self.pc += 2;
+                        self.touch(i, x + 1, false);
+                        self.i = next;
+                        self.advance(2);
                     }
 
-                    // FX75 SCHIP: Store V0 to VX in RPL user flags (X <= 7)
+                    // FX75 SCHIP: Store V0 to VX in RPL user flags (X <= 7, XO-CHIP X <= F)
                     0x0075 => {
-                        for index in 0..(cmp::max(x as usize, 7) + 1) {
-                            self.rpl_flags[index] = self.v[index];
-                        }
-                        self.pc += 2;
+                        self.rpl_flags[..=x].copy_from_slice(&self.v[..=x]);
+                        self.advance(2);
                     }
 
-                    // FX85 SCHIP: Read V0 to VX in RPL user flags (X <= 7)
+                    // FX85 SCHIP: Read V0 to VX in RPL user flags (X <= 7, XO-CHIP X <= F)
                     0x0085 => {
-                        for index in 0..(cmp::max(x as usize, 7) + 1) {
-                            self.v[index] = self.rpl_flags[index];
-                        }
+                        self.v[..=x].copy_from_slice(&self.rpl_flags[..=x]);
 
-                        self.pc += 2;
+                        self.advance(2);
                     }
-                    _ => println!("Unknown opcode: 0x00FF {:X}", self.opcode),
+                    _ => return Err(self.unknown_opcode()),
                 }
             }
-            _ => println!("Unknown opcode: {:X}", self.opcode),
+            _ => return Err(self.unknown_opcode()),
         };
+        Ok(())
     }
 
-    // Execute fn run() n times
-    pub fn step(&mut self, times: u8, display: &mut Display) {
+    // Execute fn run() n times, stopping at the first error
+    pub fn step(&mut self, times: u16) -> Result<(), Chip8Error> {
         for _ in 0..times {
-            self.run(display);
+            self.run()?;
+        }
+        Ok(())
+    }
+
+    // Hand the frame buffer to a video backend if it changed since the last call
+    pub fn render<V: Video + ?Sized>(&mut self, video: &mut V) {
+        if self.draw_flag {
+            video.draw(&self.pixels, self.display_mode);
+            self.draw_flag = false;
         }
     }
-    pub fn draw(&mut self, display: &mut Display, extended: bool) {
+
+    // Registers Vx through Vy, descending when x > y (XO-CHIP 5XY2 & 5XY3)
+    fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    fn draw(&mut self, extended: bool) -> Result<(), Chip8Error> {
         let n = (self.opcode & 0x000F) as usize; // Sprite height in bytes to be displayed;
         let w = if n == 0 && extended { 16 } else { 8 } as usize; // Sprites always 8 or 16 pixels
-        let h = if n == 0 && extended { 16 } else { n } as usize; // Height can be 0 to 16 pixels
+        let h = if n == 0 && extended { 16 } else { n }; // Height can be 0 to 16 pixels
+        let size = if extended { h * 2 } else { h }; // Sprite size in bytes per bitplane
+        self.check_access(self.i as usize, size * self.plane.count_ones() as usize)?;
 
-        // Sprite X & Y coordinates fetched from our V register
-        let x_coord = usize::from(self.v[((self.opcode & 0x0F00) >> 8) as usize]);
-        let y_coord = usize::from(self.v[((self.opcode & 0x00F0) >> 4) as usize]);
+        // CHIP-8 games only use the upper left 64x32 of the frame buffer
+        let (width, height) = if self.display_mode == DisplayMode::Extended {
+            (WIDTH, HEIGHT)
+        } else {
+            (WIDTH / 2, HEIGHT / 2)
+        };
+
+        // Sprite X & Y coordinates fetched from our V register, the origin always wraps
+        let x_coord = usize::from(self.v[((self.opcode & 0x0F00) >> 8) as usize]) % width;
+        let y_coord = usize::from(self.v[((self.opcode & 0x00F0) >> 4) as usize]) % height;
 
         self.v[0xF] = 0; // The collision flag must be off before rendering
 
-        for yline in 0..h {
-            // Fetch low & high bytes from memory if in extended (16x16 mode)
-            let pixel: u16 = if extended {
-                (self.memory[(self.i as usize + yline as usize * 2)] as u16) << 8
-                    | (self.memory[(self.i as usize + yline as usize * 2) + 1] as u16)
-            } else {
-                self.memory[(self.i as usize + yline as usize)] as u16
-            };
-
-            for xline in 0..w {
-                let x = (x_coord + xline) % WIDTH;
-                let y = (y_coord + yline) % HEIGHT;
-
-                // Evaluate our sprite by ANDing the value and shifting right by one (bit 7 -> 0).
-                // The same applies for extended: bitmask is extended to cover 16 bits.
-                if pixel & if extended { 0x8000 } else { 0x80 } >> xline != 0 {
-                    self.v[0xF] |= self.pixels[y % HEIGHT][x % WIDTH] as u8;
-                    self.pixels[y][x] ^= true;
+        // XO-CHIP: each selected bitplane takes the next sprite in memory, plane 1 first
+        let mut addr = self.i as usize;
+        for plane in [1u8, 2u8].iter().cloned() {
+            if self.plane & plane == 0 {
+                continue;
+            }
+
+            for yline in 0..h {
+                // Fetch low & high bytes from memory if in extended (16x16 mode)
+                let pixel: u16 = if extended {
+                    (self.peek(addr + yline * 2) as u16) << 8 | (self.peek(addr + yline * 2 + 1) as u16)
+                } else {
+                    self.peek(addr + yline) as u16
+                };
+
+                for xline in 0..w {
+                    // With the clipping quirk sprites are cut off at the edges instead of wrapping
+                    if self.quirks.clip && (x_coord + xline >= width || y_coord + yline >= height) {
+                        continue;
+                    }
+                    let x = (x_coord + xline) % width;
+                    let y = (y_coord + yline) % height;
+
+                    // Evaluate our sprite by ANDing the value and shifting right by one (bit 7 -> 0).
+                    // The same applies for extended: bitmask is extended to cover 16 bits.
+                    if pixel & if extended { 0x8000 } else { 0x80 } >> xline != 0 {
+                        if self.pixels[y][x] & plane != 0 {
+                            self.v[0xF] = 1;
+                        }
+                        self.pixels[y][x] ^= plane;
+                    }
                 }
             }
+            addr += size;
         }
-        // Upscale in Normal mode
-        if self.display_mode == DisplayMode::Extended {
-            display.draw(&self.pixels, 10, 10);
-        } else {
-            display.draw(&self.pixels, 20, 20);
-        }
+        self.touch(self.i as usize, addr - self.i as usize, false);
         self.draw_flag = true;
+        Ok(())
     }
 }
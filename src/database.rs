@@ -0,0 +1,131 @@
+// Per-ROM compatibility database keyed by the SHA-1 of the ROM image.
+// Reads the community chip-8-database programs.json format: https://github.com/chip-8/chip-8-database
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::quirks::Quirks;
+use crate::video::{Palette, DEFAULT_PALETTE};
+
+// Subset of programs.json covering the ROMs shipped in roms/
+const BUNDLED: &str = include_str!("../database/programs.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    tickrate: Option<u16>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: String,
+    pub quirks: Quirks,
+    pub tickrate: u16,             // Instructions per frame
+    pub palette: Option<Palette>,
+    pub keys: Vec<(String, u8)>,   // Key hints, e.g. ("left", 4)
+}
+
+pub struct Database {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl Database {
+    pub fn bundled() -> Database {
+        Database::from_json(BUNDLED).expect("Bundled ROM database is invalid")
+    }
+
+    pub fn from_json(json: &str) -> Result<Database, serde_json::Error> {
+        let programs: Vec<Program> = serde_json::from_str(json)?;
+        let mut roms = HashMap::new();
+
+        for program in programs {
+            for (hash, rom) in program.roms {
+                // Use the first platform we know how to emulate
+                let platform = rom
+                    .platforms
+                    .iter()
+                    .find(|p| Quirks::from_platform(p).is_some());
+
+                let platform = match platform {
+                    Some(platform) => platform.clone(),
+                    None => continue,
+                };
+
+                let mut quirks = Quirks::from_platform(&platform).unwrap();
+                if let Some(overrides) = rom.quirky_platforms.get(&platform) {
+                    for (name, &value) in overrides {
+                        quirks.set(name, value);
+                    }
+                }
+
+                let info = RomInfo {
+                    title: program.title.clone(),
+                    tickrate: rom.tickrate.unwrap_or_else(|| default_tickrate(&platform)),
+                    platform,
+                    quirks,
+                    palette: rom.colors.as_ref().and_then(|c| parse_palette(&c.pixels)),
+                    keys: rom.keys.into_iter().collect(),
+                };
+                roms.insert(hash.to_lowercase(), info);
+            }
+        }
+
+        Ok(Database { roms })
+    }
+
+    pub fn lookup(&self, hash: &str) -> Option<&RomInfo> {
+        self.roms.get(&hash.to_lowercase())
+    }
+}
+
+pub fn sha1(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+// Platform default speeds from the chip-8-database platforms.json
+fn default_tickrate(platform: &str) -> u16 {
+    match platform {
+        "originalChip8" | "hybridVIP" => 15,
+        "modernChip8" => 12,
+        "xochip" => 1000,
+        _ => 30,
+    }
+}
+
+// "#RRGGBB" strings, missing colours fall back to the default palette
+pub fn parse_palette(colors: &[String]) -> Option<Palette> {
+    if colors.is_empty() {
+        return None;
+    }
+
+    let mut palette = DEFAULT_PALETTE;
+    for (slot, color) in palette.iter_mut().zip(colors) {
+        let hex = color.trim_start_matches('#');
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        *slot = (channel(0)?, channel(2)?, channel(4)?);
+    }
+    Some(palette)
+}
@@ -0,0 +1,485 @@
+// Interactive debugger. The front end feeds it command lines & runs every frame through
+// Debugger::run instead of Cpu::step so it can stop on breakpoints.
+use std::collections::BTreeSet;
+
+use crate::cpu::{Access, Cpu, KeyWait};
+use crate::disasm;
+use crate::error::{Chip8Error, ErrorPolicy};
+
+const HELP: &str = "\
+c, continue      Resume execution
+p, pause         Pause execution
+s, step [n]      Execute n instructions (default 1)
+n, next          Step over a CALL (2NNN)
+f, finish        Run until the current subroutine returns
+b, break [addr]  Set a breakpoint at addr or source line (FILE:line or :line), or list them
+d, delete addr   Remove the breakpoint at addr
+w, watch range   Stop when memory in range is written, e.g. w 300-302, or list watches
+rw, rwatch range Stop when memory in range is read
+aw, awatch range Stop when memory in range is read or written
+when reg [op n]  Stop when V0-VF or I changes, or once it compares true, e.g. when VA >= 10
+uw, unwatch n    Remove watch n
+r, regs          Show registers, stack & timers
+bt, backtrace    Show the chain of active CALLs, innermost last
+t, trace         Print every executed instruction
+h, help          Show this help
+Numbers & addresses are hex, written as 2A0, #2A0 or 0x2A0";
+
+// Where a resumed run should stop again
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    None,
+    Return { pc: u16, sp: u16 }, // Step over, back at the instruction after the CALL
+    Finish { sp: u16 },          // Run to return, the stack dropped below sp
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    V(usize),
+    I,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Watch {
+    // Inclusive address range, stops on reads, writes or both
+    Memory { start: u16, end: u16, read: bool, write: bool },
+    // Stops when the register changes, or when the comparison turns true
+    Register { reg: Register, cond: Option<(Compare, u16)> },
+}
+
+pub struct Debugger {
+    pub paused: bool,
+    pub breakpoints: BTreeSet<u16>,
+    pub watches: Vec<Watch>,
+    target: Target,
+    resumed: bool, // Don't stop on the breakpoint we are resuming from
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            paused: false,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            target: Target::None,
+            resumed: false,
+        }
+    }
+
+    pub fn pause(&mut self, cpu: &Cpu) {
+        Self::show(cpu);
+        if !self.paused {
+            println!("Paused, type h for help");
+        }
+        self.paused = true;
+        self.target = Target::None;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.resumed = true;
+    }
+
+    // Break in or carry on, bound to F12
+    pub fn toggle(&mut self, cpu: &Cpu) {
+        if self.paused {
+            println!("Running");
+            self.resume();
+        } else {
+            self.pause(cpu);
+        }
+    }
+
+    // Execute up to cycles instructions, stopping early on a breakpoint, watch or step target.
+    // Errors pause the debugger with the Trap policy & are returned otherwise.
+    pub fn run(&mut self, cpu: &mut Cpu, cycles: u16) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            if self.paused {
+                return Ok(());
+            }
+            if !self.resumed && self.breakpoints.contains(&cpu.pc()) {
+                println!("Breakpoint at {}", cpu.location(cpu.pc()));
+                self.pause(cpu);
+                return Ok(());
+            }
+            self.resumed = false;
+
+            let hit = match self.execute(cpu) {
+                Ok(hit) => hit,
+                Err(e) if cpu.on_error == ErrorPolicy::Trap => {
+                    println!("{}", e);
+                    self.pause(cpu);
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+            let done = hit || match self.target {
+                Target::None => false,
+                Target::Return { pc, sp } => cpu.pc() == pc && cpu.sp() == sp,
+                Target::Finish { sp } => cpu.sp() < sp,
+            };
+            if done {
+                self.pause(cpu);
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn command(&mut self, line: &str, cpu: &mut Cpu) {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => return,
+        };
+        let arg = words.next();
+
+        match cmd {
+            "c" | "continue" => {
+                println!("Running");
+                self.resume();
+            }
+            "p" | "pause" => self.pause(cpu),
+            "s" | "step" => {
+                let count = match arg.map(str::parse::<u32>) {
+                    None => 1,
+                    Some(Ok(n)) => n,
+                    Some(Err(_)) => {
+                        println!("Invalid step count: {}", arg.unwrap_or_default());
+                        return;
+                    }
+                };
+                for _ in 0..count {
+                    match self.execute(cpu) {
+                        Ok(false) => {}
+                        Ok(true) => break,
+                        Err(e) => {
+                            println!("{}", e);
+                            break;
+                        }
+                    }
+                }
+                self.pause(cpu);
+            }
+            "n" | "next" => {
+                let opcode = Self::opcode_at(cpu, cpu.pc());
+                if opcode & 0xF000 == 0x2000 {
+                    // The return address wraps at the end of memory like the CPU's PC
+                    let pc = (cpu.pc() as usize + 2) & (cpu.quirks.memory_size - 1);
+                    self.target = Target::Return { pc: pc as u16, sp: cpu.sp() };
+                    self.resume();
+                } else {
+                    if let Err(e) = self.execute(cpu) {
+                        println!("{}", e);
+                    }
+                    self.pause(cpu);
+                }
+            }
+            "f" | "finish" => {
+                if cpu.sp() == 0 {
+                    println!("Not in a subroutine");
+                    return;
+                }
+                self.target = Target::Finish { sp: cpu.sp() };
+                self.resume();
+            }
+            "b" | "break" => match arg {
+                None => {
+                    for addr in &self.breakpoints {
+                        println!("{}  {}", cpu.location(*addr), disasm::disassemble(cpu.memory(), *addr as usize));
+                    }
+                }
+                Some(arg) => match Self::parse_location(arg, cpu) {
+                    Some(addr) => {
+                        self.breakpoints.insert(addr);
+                        println!("Breakpoint set at {}", cpu.location(addr));
+                    }
+                    None => println!("Invalid address: {}", arg),
+                },
+            },
+            "d" | "delete" => match arg.and_then(|arg| Self::parse_location(arg, cpu)) {
+                Some(addr) if self.breakpoints.remove(&addr) => {
+                    println!("Breakpoint removed at #{:04X}", addr)
+                }
+                Some(addr) => println!("No breakpoint at #{:04X}", addr),
+                None => println!("Usage: delete addr"),
+            },
+            "w" | "watch" if arg.is_none() => {
+                for (n, watch) in self.watches.iter().enumerate() {
+                    println!("{}: {}", n, Self::describe(watch));
+                }
+            }
+            "w" | "watch" | "rw" | "rwatch" | "aw" | "awatch" => {
+                let range = arg.and_then(Self::parse_range);
+                let (start, end) = match range {
+                    Some(range) => range,
+                    None => {
+                        println!("Usage: {} start[-end]", cmd);
+                        return;
+                    }
+                };
+                let read = cmd.starts_with('r') || cmd.starts_with('a');
+                let write = !cmd.starts_with('r');
+                self.add_watch(Watch::Memory { start, end, read, write });
+            }
+            "when" => match Self::parse_condition(arg, words.next(), words.next()) {
+                Some(watch) => self.add_watch(watch),
+                None => println!("Usage: when V0-VF|I [== != < <= > >= value]"),
+            },
+            "uw" | "unwatch" => match arg.and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n < self.watches.len() => {
+                    let watch = self.watches.remove(n);
+                    println!("Removed {}", Self::describe(&watch));
+                }
+                _ => println!("Usage: unwatch n, see w for the list"),
+            },
+            "r" | "regs" => Self::show(cpu),
+            "bt" | "backtrace" => {
+                for (depth, &addr) in cpu.stack().iter().enumerate() {
+                    println!("{}: {}  {}", depth, cpu.location(addr), disasm::disassemble(cpu.memory(), addr as usize));
+                }
+                println!("{}: {}", cpu.sp(), cpu.location(cpu.pc()));
+            }
+            "t" | "trace" => {
+                cpu.mode.debug = !cpu.mode.debug;
+                println!("Trace: {}", cpu.mode.debug);
+            }
+            "h" | "help" => println!("{}", HELP),
+            _ => println!("Unknown command: {}, type h for help", cmd),
+        }
+    }
+
+    fn add_watch(&mut self, watch: Watch) {
+        println!("{}: {}", self.watches.len(), Self::describe(&watch));
+        self.watches.push(watch);
+    }
+
+    // Execute one instruction, returns true when a watch fired
+    fn execute(&self, cpu: &mut Cpu) -> Result<bool, Chip8Error> {
+        let pc = cpu.pc();
+        let v = *cpu.v();
+        let i = cpu.i();
+
+        cpu.run()?;
+
+        let access = cpu.last_access();
+        for watch in &self.watches {
+            let hit = match *watch {
+                Watch::Memory { start, end, read, write } => match access {
+                    Some(Access { addr, len, write: w }) => {
+                        (if w { write } else { read })
+                            && len > 0
+                            && addr <= end as usize
+                            && addr + len > start as usize
+                    }
+                    None => false,
+                },
+                Watch::Register { reg, cond } => {
+                    let before = Self::register(reg, &v, i);
+                    let after = Self::register(reg, cpu.v(), cpu.i());
+                    match cond {
+                        None => before != after,
+                        Some((cmp, n)) => !Self::compare(cmp, before, n) && Self::compare(cmp, after, n),
+                    }
+                }
+            };
+            if hit {
+                match access {
+                    Some(a) if matches!(watch, Watch::Memory { .. }) => println!(
+                        "{} #{:04X}-#{:04X} by {}, watch {}",
+                        if a.write { "Write to" } else { "Read from" },
+                        a.addr,
+                        a.addr + a.len - 1,
+                        cpu.location(pc),
+                        Self::describe(watch)
+                    ),
+                    _ => println!("Hit {} at {}", Self::describe(watch), cpu.location(pc)),
+                }
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn register(reg: Register, v: &[u8; 16], i: u16) -> u16 {
+        match reg {
+            Register::V(x) => v[x] as u16,
+            Register::I => i,
+        }
+    }
+
+    fn compare(cmp: Compare, a: u16, b: u16) -> bool {
+        match cmp {
+            Compare::Eq => a == b,
+            Compare::Ne => a != b,
+            Compare::Lt => a < b,
+            Compare::Le => a <= b,
+            Compare::Gt => a > b,
+            Compare::Ge => a >= b,
+        }
+    }
+
+    fn describe(watch: &Watch) -> String {
+        match *watch {
+            Watch::Memory { start, end, read, write } => {
+                let kind = match (read, write) {
+                    (true, true) => "access",
+                    (true, false) => "read",
+                    _ => "write",
+                };
+                format!("{} #{:04X}-#{:04X}", kind, start, end)
+            }
+            Watch::Register { reg, cond } => {
+                let name = match reg {
+                    Register::V(x) => format!("V{:X}", x),
+                    Register::I => "I".to_string(),
+                };
+                match cond {
+                    None => format!("{} changes", name),
+                    Some((cmp, n)) => {
+                        let op = match cmp {
+                            Compare::Eq => "==",
+                            Compare::Ne => "!=",
+                            Compare::Lt => "<",
+                            Compare::Le => "<=",
+                            Compare::Gt => ">",
+                            Compare::Ge => ">=",
+                        };
+                        format!("{} {} #{:X}", name, op, n)
+                    }
+                }
+            }
+        }
+    }
+
+    // Registers, I, SP, stack, timers & the instruction about to execute
+    pub fn show(cpu: &Cpu) {
+        let v = cpu.v();
+        for row in 0..2 {
+            let regs: Vec<String> = (row * 8..row * 8 + 8)
+                .map(|r| format!("V{:X}: {:02X}", r, v[r]))
+                .collect();
+            println!("{}", regs.join("  "));
+        }
+
+        println!(
+            "I: #{:04X}  SP: {}  DT: {}  ST: {}",
+            cpu.i(),
+            cpu.sp(),
+            cpu.delay_timer(),
+            cpu.sound_timer()
+        );
+        let stack: Vec<String> = cpu.stack().iter().map(|a| format!("#{:04X}", a)).collect();
+        println!("Stack: [{}]", stack.join(", "));
+        println!("Key wait: {}", Self::key_wait(cpu));
+
+        let pc = cpu.pc();
+        println!(
+            "{}: {:04X}  {}",
+            cpu.location(pc),
+            Self::opcode_at(cpu, pc),
+            disasm::disassemble(cpu.memory(), pc as usize)
+        );
+    }
+
+    // FX0A stays on its own instruction until the key is released, so X comes from the opcode at PC
+    pub fn key_wait(cpu: &Cpu) -> String {
+        let x = (Self::opcode_at(cpu, cpu.pc()) >> 8) & 0xF;
+        match cpu.key_wait {
+            KeyWait::Idle => "none".to_string(),
+            KeyWait::Waiting => format!("V{:X}, waiting for a key press", x),
+            KeyWait::Held(key) => format!("V{:X}, waiting for key {:X} to be released", x, key),
+        }
+    }
+
+    fn opcode_at(cpu: &Cpu, addr: u16) -> u16 {
+        let memory = cpu.memory();
+        let addr = addr as usize;
+        if addr + 1 < memory.len() {
+            (memory[addr] as u16) << 8 | memory[addr + 1] as u16
+        } else {
+            0
+        }
+    }
+
+    // A single address or start-end, both inclusive
+    fn parse_range(text: &str) -> Option<(u16, u16)> {
+        match text.find('-') {
+            Some(dash) => {
+                let start = Self::parse_addr(&text[..dash])?;
+                let end = Self::parse_addr(&text[dash + 1..])?;
+                if start <= end {
+                    Some((start, end))
+                } else {
+                    None
+                }
+            }
+            None => Self::parse_addr(text).map(|addr| (addr, addr)),
+        }
+    }
+
+    // `when VA`, `when I >= 300`
+    fn parse_condition(reg: Option<&str>, op: Option<&str>, value: Option<&str>) -> Option<Watch> {
+        let reg = reg?.to_uppercase();
+        let reg = if reg == "I" {
+            Register::I
+        } else if reg.len() == 2 && reg.starts_with('V') {
+            Register::V(usize::from_str_radix(&reg[1..], 16).ok()?)
+        } else {
+            return None;
+        };
+
+        let cond = match op {
+            None => None,
+            Some(op) => {
+                let cmp = match op {
+                    "==" => Compare::Eq,
+                    "!=" => Compare::Ne,
+                    "<" => Compare::Lt,
+                    "<=" => Compare::Le,
+                    ">" => Compare::Gt,
+                    ">=" => Compare::Ge,
+                    _ => return None,
+                };
+                Some((cmp, Self::parse_addr(value?)?))
+            }
+        };
+        Some(Watch::Register { reg, cond })
+    }
+
+    // An address, or a source line as FILE:line or :line when running assembly source
+    fn parse_location(text: &str, cpu: &Cpu) -> Option<u16> {
+        match text.rfind(':') {
+            Some(colon) => {
+                let line = text[colon + 1..].parse().ok()?;
+                cpu.source.as_ref()?.addr(line)
+            }
+            None => Self::parse_addr(text),
+        }
+    }
+
+    // Addresses are hex, written as 2A0, #2A0 or 0x2A0
+    fn parse_addr(text: &str) -> Option<u16> {
+        let digits = text
+            .trim_start_matches('#')
+            .trim_start_matches("0x")
+            .trim_start_matches("0X");
+        u16::from_str_radix(digits, 16).ok()
+    }
+}
@@ -0,0 +1,288 @@
+// Decode opcodes into CHIPPER style mnemonics, the syntax used by roms/SOURCES/*.SRC.
+// Mirrors the decoding in Cpu::run, XO-CHIP opcodes use the names chip8-asm accepts after OPTION XOCHIP.
+use std::collections::{BTreeMap, BTreeSet};
+
+// ROMs are loaded at 0x200
+const START: usize = 0x200;
+
+// Instruction length in bytes, XO-CHIP F000 NNNN is the only 4 byte instruction
+pub fn length(opcode: u16) -> u16 {
+    if opcode == 0xF000 {
+        4
+    } else {
+        2
+    }
+}
+
+// `next` is the word following the opcode, only used by F000 NNNN
+pub fn decode(opcode: u16, next: u16) -> Option<String> {
+    decode_with(opcode, next, &|addr| format!("#{:03X}", addr))
+}
+
+// Like decode, with addresses formatted by `addr` so they can be replaced by labels
+fn decode_with(opcode: u16, next: u16, addr: &dyn Fn(u16) -> String) -> Option<String> {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nnn = opcode & 0x0FFF;
+    let kk = opcode & 0x00FF;
+
+    let text = match opcode & 0xF000 {
+        0x0000 if x == 0 => match opcode & 0x00F0 {
+            0x00C0 => format!("SCD  {}", n),
+            0x00D0 => format!("SCU  {}", n),
+            _ => match opcode {
+                0x00E0 => "CLS".to_string(),
+                0x00EE => "RET".to_string(),
+                0x00FB => "SCR".to_string(),
+                0x00FC => "SCL".to_string(),
+                0x00FD => "EXIT".to_string(),
+                0x00FE => "LOW".to_string(),
+                0x00FF => "HIGH".to_string(),
+                _ => return None,
+            },
+        },
+        0x1000 => format!("JP   {}", addr(nnn)),
+        0x2000 => format!("CALL {}", addr(nnn)),
+        0x3000 => format!("SE   V{:X}, #{:02X}", x, kk),
+        0x4000 => format!("SNE  V{:X}, #{:02X}", x, kk),
+        0x5000 => match n {
+            0x0 => format!("SE   V{:X}, V{:X}", x, y),
+            0x2 => format!("SAVE V{:X}, V{:X}", x, y),
+            0x3 => format!("LOAD V{:X}, V{:X}", x, y),
+            _ => return None,
+        },
+        0x6000 => format!("LD   V{:X}, #{:02X}", x, kk),
+        0x7000 => format!("ADD  V{:X}, #{:02X}", x, kk),
+        0x8000 => {
+            let op = match n {
+                0x0 => "LD  ",
+                0x1 => "OR  ",
+                0x2 => "AND ",
+                0x3 => "XOR ",
+                0x4 => "ADD ",
+                0x5 => "SUB ",
+                0x6 => "SHR ",
+                0x7 => "SUBN",
+                0xE => "SHL ",
+                _ => return None,
+            };
+            format!("{} V{:X}, V{:X}", op, x, y)
+        }
+        0x9000 if n == 0 => format!("SNE  V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD   I, {}", addr(nnn)),
+        0xB000 => format!("JP   V0, {}", addr(nnn)),
+        0xC000 => format!("RND  V{:X}, #{:02X}", x, kk),
+        0xD000 => format!("DRW  V{:X}, V{:X}, {}", x, y, n),
+        0xE000 => match kk {
+            0x9E => format!("SKP  V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => return None,
+        },
+        0xF000 => match kk {
+            0x00 if x == 0 => format!("LD   I, LONG {}", addr(next)),
+            0x01 => format!("PLANE {}", x),
+            0x02 if x == 0 => "AUDIO".to_string(),
+            0x07 => format!("LD   V{:X}, DT", x),
+            0x0A => format!("LD   V{:X}, K", x),
+            0x15 => format!("LD   DT, V{:X}", x),
+            0x18 => format!("LD   ST, V{:X}", x),
+            0x1E => format!("ADD  I, V{:X}", x),
+            0x29 => format!("LD   F, V{:X}", x),
+            0x30 => format!("LD   HF, V{:X}", x),
+            0x33 => format!("LD   B, V{:X}", x),
+            0x3A => format!("PITCH V{:X}", x),
+            0x55 => format!("LD   [I], V{:X}", x),
+            0x65 => format!("LD   V{:X}, [I]", x),
+            0x75 => format!("LD   R, V{:X}", x),
+            0x85 => format!("LD   V{:X}, R", x),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(text)
+}
+
+// Decode the instruction at addr, unknown opcodes are shown as data words
+pub fn disassemble(memory: &[u8], addr: usize) -> String {
+    let word = |a: usize| {
+        if a + 1 < memory.len() {
+            (memory[a] as u16) << 8 | memory[a + 1] as u16
+        } else {
+            0
+        }
+    };
+    let opcode = word(addr);
+
+    decode(opcode, word(addr + 2)).unwrap_or_else(|| format!("DW   #{:04X}", opcode))
+}
+
+// Where execution can go after an instruction, used to separate code from data
+enum Flow {
+    Next,          // Falls through to the next instruction
+    Skip,          // Falls through or skips the next instruction
+    Jump(u16),     // 1NNN
+    Call(u16),     // 2NNN, returns to the next instruction
+    Stop,          // 00EE, 00FD & BNNN, the target is not known statically
+}
+
+fn flow(opcode: u16) -> Flow {
+    let nnn = opcode & 0x0FFF;
+    match opcode & 0xF000 {
+        0x0000 if opcode == 0x00EE || opcode == 0x00FD => Flow::Stop,
+        0x1000 => Flow::Jump(nnn),
+        0x2000 => Flow::Call(nnn),
+        0x3000 | 0x4000 | 0x5000 | 0x9000 => Flow::Skip,
+        0xB000 => Flow::Stop,
+        0xE000 => Flow::Skip,
+        _ => Flow::Next,
+    }
+}
+
+// SCHIP opcodes, the output needs OPTION SCHIP11 when any of these are used
+fn is_schip(opcode: u16) -> bool {
+    match opcode & 0xF0FF {
+        0xF030 | 0xF075 | 0xF085 => true,
+        _ => opcode & 0xFFF0 == 0x00C0 || (0x00FB..=0x00FF).contains(&opcode) || opcode & 0xF00F == 0xD000,
+    }
+}
+
+// XO-CHIP opcodes, the output needs OPTION XOCHIP when any of these are used
+fn is_xochip(opcode: u16) -> bool {
+    match opcode & 0xF00F {
+        0x5002 | 0x5003 => true,
+        _ => opcode & 0xFFF0 == 0x00D0 || opcode == 0xF000 || opcode == 0xF002 || matches!(opcode & 0xF0FF, 0xF001 | 0xF03A),
+    }
+}
+
+// Disassemble a whole ROM loaded at 0x200 into CHIPPER source.
+// Code is found by following jumps & calls from the entry point, everything else is emitted as data.
+pub fn disassemble_rom(rom: &[u8]) -> String {
+    let end = START + rom.len();
+    let word = |a: usize| {
+        if a >= START && a + 1 < end {
+            (rom[a - START] as u16) << 8 | rom[a + 1 - START] as u16
+        } else {
+            0
+        }
+    };
+    let in_rom = |a: usize| a >= START && a + 1 < end;
+
+    // Trace code from the entry point
+    let mut code: BTreeMap<usize, u16> = BTreeMap::new(); // Instruction start -> length
+    let mut covered = vec![false; rom.len()];
+    let mut jumps = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut data = BTreeSet::new();
+    let mut todo = vec![START];
+    let mut schip = false;
+    let mut xochip = false;
+
+    while let Some(pc) = todo.pop() {
+        if !in_rom(pc) || covered[pc - START] {
+            continue;
+        }
+        let opcode = word(pc);
+        if decode(opcode, word(pc + 2)).is_none() {
+            continue;
+        }
+        let len = length(opcode) as usize;
+        if pc + len > end || covered[pc - START..pc - START + len].iter().any(|&c| c) {
+            continue;
+        }
+        for c in &mut covered[pc - START..pc - START + len] {
+            *c = true;
+        }
+        code.insert(pc, len as u16);
+        schip |= is_schip(opcode);
+        xochip |= is_xochip(opcode);
+
+        // I targets are data, unless they turn out to be code
+        if opcode & 0xF000 == 0xA000 {
+            data.insert((opcode & 0x0FFF) as usize);
+        } else if opcode == 0xF000 {
+            data.insert(word(pc + 2) as usize);
+        }
+
+        let next = pc + len;
+        match flow(opcode) {
+            Flow::Next => todo.push(next),
+            Flow::Skip => {
+                todo.push(next);
+                todo.push(next + length(word(next)) as usize);
+            }
+            Flow::Jump(target) => {
+                jumps.insert(target as usize);
+                todo.push(target as usize);
+            }
+            Flow::Call(target) => {
+                calls.insert(target as usize);
+                todo.push(target as usize);
+                todo.push(next);
+            }
+            Flow::Stop => {}
+        }
+    }
+
+    // Label every referenced address inside the ROM, subroutines first, then jumps, then data
+    let mut labels: BTreeMap<usize, String> = BTreeMap::new();
+    for (set, prefix) in [(&calls, "SUB"), (&jumps, "L"), (&data, "DATA")].iter() {
+        for &addr in set.iter() {
+            if addr >= START && addr < end && !labels.contains_key(&addr) {
+                labels.insert(addr, format!("{}_{:03X}", prefix, addr));
+            }
+        }
+    }
+    let label = |addr: u16| match labels.get(&(addr as usize)) {
+        Some(name) => name.clone(),
+        None => format!("#{:03X}", addr),
+    };
+
+    let mut out = String::new();
+    out.push_str("; Disassembled by chip8-disasm\n\n");
+    out.push_str("OPTION BINARY\n");
+    out.push_str("ALIGN OFF\n");
+    if xochip {
+        out.push_str("OPTION XOCHIP\n");
+    } else if schip {
+        out.push_str("OPTION SCHIP11\n");
+    }
+    out.push('\n');
+
+    // Labels pointing into the middle of an instruction can't be placed, define them by address
+    let mut starts = BTreeSet::new();
+    let mut addr = START;
+    while addr < end {
+        starts.insert(addr);
+        addr += code.get(&addr).map_or(1, |&len| len as usize);
+    }
+    for (addr, name) in &labels {
+        if !starts.contains(addr) {
+            out.push_str(&format!("{} EQU #{:03X}\n", name, addr));
+        }
+    }
+
+    let mut addr = START;
+    while addr < end {
+        if let Some(name) = labels.get(&addr) {
+            out.push_str(&format!("\n{}:\n", name));
+        }
+        match code.get(&addr) {
+            Some(&len) => {
+                let text = decode_with(word(addr), word(addr + 2), &label).unwrap_or_default();
+                out.push_str(&format!("    {:<24}; {:03X}\n", text, addr));
+                addr += len as usize;
+            }
+            None => {
+                let byte = rom[addr - START];
+                let bits: String = (0..8)
+                    .map(|bit| if byte & (0x80 >> bit) != 0 { '1' } else { '.' })
+                    .collect();
+                let text = format!("DB   ${}", bits);
+                out.push_str(&format!("    {:<24}; {:03X}\n", text, addr));
+                addr += 1;
+            }
+        }
+    }
+    out
+}
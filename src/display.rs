@@ -2,72 +2,76 @@ use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::Sdl;
 
-pub const WIDTH: usize = 128;
-pub const HEIGHT: usize = 64;
-pub const SCALE_FACTOR: u32 = 10;
+use chip8::video::{DisplayMode, Palette, Pixels, Video, DEFAULT_PALETTE, HEIGHT, WIDTH};
 
-#[derive(PartialEq, Debug)]
-pub enum DisplayMode {
-    Normal,
-    Extended,
-}
+// Default window scale, a SCHIP pixel is SCALE_FACTOR x SCALE_FACTOR
+pub const SCALE_FACTOR: u32 = 10;
 
 pub struct Display<'a> {
     pub renderer: sdl2::render::Renderer<'a>,
-    pub pixels: [[bool; WIDTH]; HEIGHT],
-    pub draw_flag: bool,
+    pub palette: Palette,
+    scale: u32,
 }
 
 impl<'a> Display<'a> {
-    pub fn new(sdl_context: &Sdl) -> Display<'a> {
+    pub fn new(sdl_context: &Sdl, scale: u32, fullscreen: bool) -> Display<'a> {
         // Initialize SDL2
         let video = sdl_context.video().expect("SDL2 initialization failed");
 
         // Create window
-        let window = video
-            .window(
-                "Chip-8",
-                WIDTH as u32 * SCALE_FACTOR,
-                HEIGHT as u32 * SCALE_FACTOR,
-            )
-            .position_centered()
-            .build()
-            .expect("Window creation failed");
-        let renderer = window
+        let (width, height) = (WIDTH as u32 * scale, HEIGHT as u32 * scale);
+        let mut builder = video.window("Chip-8", width, height);
+        builder.position_centered();
+        if fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let window = builder.build().expect("Window creation failed");
+        let mut renderer = window
             .renderer()
             .accelerated()
             .build()
             .expect("Initialization of window renderer failed");
 
+        // Fullscreen stretches the window sized picture over the desktop, keeping the aspect ratio
+        renderer
+            .set_logical_size(width, height)
+            .expect("Setting the renderer size failed");
+
         Display {
             renderer,
-            pixels: [[false; WIDTH]; HEIGHT],
-            draw_flag: true,
+            palette: DEFAULT_PALETTE,
+            scale,
         }
     }
 
-    pub fn draw(&mut self, pixels: &[[bool; WIDTH]; HEIGHT], clamp_pos: i32, clamp_size: u32) {
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                if pixels[y][x] {
-                    // Foreground
-                    self.renderer.set_draw_color(Color::RGB(251, 241, 199));
-                } else {
-                    // Background
-                    self.renderer.set_draw_color(Color::RGB(69, 133, 149));
-                }
+    fn draw_scaled(&mut self, pixels: &Pixels, clamp_pos: i32, clamp_size: u32) {
+        for (y, row) in pixels.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                // Background, foreground & the XO-CHIP second bitplane and overlap colours
+                let (r, g, b) = self.palette[(pixel & 0x3) as usize];
+                self.renderer.set_draw_color(Color::RGB(r, g, b));
                 // x, y, w, h
                 self.renderer
                     .fill_rect(Rect::new(
                         x as i32 * clamp_pos,
                         y as i32 * clamp_pos,
-                        clamp_size as u32,
-                        clamp_size as u32,
+                        clamp_size,
+                        clamp_size,
                     ))
                     .unwrap();
             }
         }
         self.renderer.present();
-        self.draw_flag = true;
+    }
+}
+
+impl<'a> Video for Display<'a> {
+    fn draw(&mut self, pixels: &Pixels, mode: DisplayMode) {
+        // Upscale in Normal mode
+        if mode == DisplayMode::Extended {
+            self.draw_scaled(pixels, self.scale as i32, self.scale);
+        } else {
+            self.draw_scaled(pixels, self.scale as i32 * 2, self.scale * 2);
+        }
     }
 }
@@ -0,0 +1,87 @@
+// Errors from loading & running programs
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::asm::AsmError;
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    RomTooLarge { size: usize, max: usize },
+    Io(io::Error),
+    Asm(AsmError),                              // Assembling a source file failed
+    UnknownOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16, calls: Vec<u16> }, // CALL with a full stack, calls holds the active CALLs
+    StackUnderflow { pc: u16 },                 // RET with an empty stack
+    MemoryOutOfBounds { pc: u16, addr: usize }, // First address past the end of memory
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is too large, size: {} bytes, max: {} bytes", size, max)
+            }
+            Chip8Error::Io(e) => write!(f, "{}", e),
+            Chip8Error::Asm(e) => write!(f, "{}", e),
+            Chip8Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unknown opcode {:04X} at #{:04X}", opcode, pc)
+            }
+            Chip8Error::StackOverflow { pc, calls } => {
+                write!(f, "Stack overflow at #{:04X}, {} calls deep. Call chain:", pc, calls.len())?;
+                for addr in calls {
+                    write!(f, " #{:04X} >", addr)?;
+                }
+                write!(f, " #{:04X}", pc)
+            }
+            Chip8Error::StackUnderflow { pc } => write!(f, "Stack underflow at #{:04X}", pc),
+            Chip8Error::MemoryOutOfBounds { pc, addr } => {
+                write!(f, "Memory access out of bounds (#{:X}) at #{:04X}", addr, pc)
+            }
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(e) => Some(e),
+            Chip8Error::Asm(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
+
+impl From<AsmError> for Chip8Error {
+    fn from(e: AsmError) -> Self {
+        Chip8Error::Asm(e)
+    }
+}
+
+// What to do when an instruction fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorPolicy {
+    Halt, // Stop the machine on the failing instruction
+    Skip, // Print the error & carry on with the next instruction
+    Trap, // Break into the debugger on the failing instruction
+}
+
+// Names selectable from the command line
+pub const POLICIES: [&str; 3] = ["halt", "skip", "trap"];
+
+impl ErrorPolicy {
+    pub fn from_name(name: &str) -> Option<ErrorPolicy> {
+        match name {
+            "halt" => Some(ErrorPolicy::Halt),
+            "skip" => Some(ErrorPolicy::Skip),
+            "trap" => Some(ErrorPolicy::Trap),
+            _ => None,
+        }
+    }
+}
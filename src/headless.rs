@@ -0,0 +1,60 @@
+// Run ROMs without a window, for automated tests & golden image checks.
+// Screens are compared as text: one line per row, `.` for off, `#` for plane 1 & digits 2-3 for XO-CHIP planes.
+use crate::cpu::Cpu;
+use crate::error::Chip8Error;
+use crate::video::{DisplayMode, Frame, Video, HEIGHT, WIDTH};
+
+// update_timers takes milliseconds, one 60 Hz frame
+pub const FRAME_MS: f32 = 1000.0 / 60.0;
+
+// Same frame loop as the SDL front end: speed instructions, the frame handed to video, then the timers.
+// A failing instruction still renders what was drawn before it.
+pub fn run_frames<V: Video + ?Sized>(cpu: &mut Cpu, frames: u32, video: &mut V) -> Result<(), Chip8Error> {
+    for _ in 0..frames {
+        let result = cpu.step(cpu.speed);
+        cpu.render(video);
+        result?;
+        cpu.update_timers(FRAME_MS);
+    }
+    Ok(())
+}
+
+// A rendered frame as text, 64x32 in lores & 128x64 in hires
+pub fn screen(frame: &Frame) -> String {
+    let (width, height) = match frame.mode {
+        DisplayMode::Extended => (WIDTH, HEIGHT),
+        DisplayMode::Normal => (WIDTH / 2, HEIGHT / 2),
+    };
+
+    let mut text = String::with_capacity((width + 1) * height);
+    for row in frame.pixels.iter().take(height) {
+        for &pixel in row.iter().take(width) {
+            text.push(match pixel {
+                0 => '.',
+                1 => '#',
+                p => (b'0' + p) as char,
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// None when the screens match, otherwise a description of the first difference
+pub fn diff(actual: &str, golden: &str) -> Option<String> {
+    let actual: Vec<&str> = actual.lines().collect();
+    let golden: Vec<&str> = golden.lines().collect();
+    if actual.len() != golden.len() {
+        return Some(format!("screen has {} rows, golden image has {}", actual.len(), golden.len()));
+    }
+
+    let rows: Vec<usize> = (0..actual.len()).filter(|&y| actual[y] != golden[y]).collect();
+    let first = *rows.first()?;
+    Some(format!(
+        "{} rows differ, first at row {}:\n  got      {}\n  expected {}",
+        rows.len(),
+        first,
+        actual[first],
+        golden[first]
+    ))
+}
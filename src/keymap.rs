@@ -0,0 +1,126 @@
+// Keyboard bindings for the 16 keypad keys & the emulator hotkeys.
+// Keys are SDL scancode names ("1", "Q", "Left", "Page Up", "F12"...), the front end resolves them.
+use std::collections::BTreeMap;
+
+// Emulator functions bound to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hotkey {
+    Exit,
+    Faster,
+    Slower,
+    Debug,
+    Reset,
+    Mute,
+    Save,
+    Load,
+    NextSlot,
+    Rewind, // Held down rather than pressed
+}
+
+// Names used in the config file, in Hotkey order
+pub const HOTKEYS: [&str; 10] = ["exit", "faster", "slower", "debug", "reset", "mute", "save", "load", "next_slot", "rewind"];
+
+// Named keypad layouts selectable from the command line
+pub const KEYMAPS: [&str; 2] = ["vip", "hex"];
+
+impl Hotkey {
+    pub const ALL: [Hotkey; 10] = [
+        Hotkey::Exit,
+        Hotkey::Faster,
+        Hotkey::Slower,
+        Hotkey::Debug,
+        Hotkey::Reset,
+        Hotkey::Mute,
+        Hotkey::Save,
+        Hotkey::Load,
+        Hotkey::NextSlot,
+        Hotkey::Rewind,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Hotkey> {
+        HOTKEYS.iter().position(|&n| n == name).map(|i| Hotkey::ALL[i])
+    }
+
+    pub fn name(self) -> &'static str {
+        HOTKEYS[self as usize]
+    }
+
+    fn default_key(self) -> &'static str {
+        match self {
+            Hotkey::Exit => "Escape",
+            Hotkey::Faster => "Page Up",
+            Hotkey::Slower => "Page Down",
+            Hotkey::Debug => "F12",
+            Hotkey::Reset => "F3",
+            Hotkey::Mute => "M",
+            Hotkey::Save => "F5",
+            Hotkey::Load => "F7",
+            Hotkey::NextSlot => "F6",
+            Hotkey::Rewind => "Backspace",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    pub keys: [String; 16], // Key bound to each hex key 0-F
+    pub hotkeys: BTreeMap<Hotkey, String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::vip()
+    }
+}
+
+impl Keymap {
+    // The COSMAC VIP keypad laid over the left of the keyboard:
+    // 1 2 3 C    1 2 3 4
+    // 4 5 6 D    Q W E R
+    // 7 8 9 E    A S D F
+    // A 0 B F    Z X C V
+    pub fn vip() -> Keymap {
+        Keymap::with_keys(["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"])
+    }
+
+    // Each hex key on the key with the same name, 0-9 & A-F
+    pub fn hex() -> Keymap {
+        Keymap::with_keys(["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F"])
+    }
+
+    fn with_keys(keys: [&str; 16]) -> Keymap {
+        Keymap {
+            keys: keys.map(String::from),
+            hotkeys: Hotkey::ALL.iter().map(|&h| (h, h.default_key().to_string())).collect(),
+        }
+    }
+
+    pub fn from_preset(name: &str) -> Option<Keymap> {
+        match name.to_lowercase().as_str() {
+            "vip" | "cosmac" => Some(Keymap::vip()),
+            "hex" => Some(Keymap::hex()),
+            _ => None,
+        }
+    }
+
+    // A key bound twice can't tell the two apart, returns a description of the first clash
+    pub fn clash(&self) -> Option<String> {
+        let mut bound: Vec<(String, String)> = Vec::new();
+        let keys = self.keys.iter().enumerate().map(|(k, name)| (format!("key {:X}", k), name));
+        let hotkeys = self.hotkeys.iter().map(|(h, name)| (h.name().to_string(), name));
+
+        for (what, name) in keys.chain(hotkeys) {
+            let name = name.to_lowercase();
+            if let Some((other, _)) = bound.iter().find(|(_, n)| *n == name) {
+                return Some(format!("{} is bound to both {} & {}", name, other, what));
+            }
+            bound.push((what, name));
+        }
+        None
+    }
+}
+
+// Hex digit of a `[keys]` entry in the config file
+pub fn parse_key(text: &str) -> Option<u8> {
+    u8::from_str_radix(text, 16).ok().filter(|&key| key < 16)
+}
@@ -1,12 +1,17 @@
 extern crate sdl2;
 
 use sdl2::event::Event;
-use sdl2::keyboard::{KeyboardState, Keycode, Scancode};
+use sdl2::keyboard::{KeyboardState, Scancode};
 use sdl2::EventPump;
 use sdl2::Sdl;
 
+use chip8::keymap::{Hotkey, Keymap};
+
 pub struct Keypad {
     pump: EventPump,
+    keys: [Scancode; 16],
+    hotkeys: Vec<(Scancode, Hotkey)>,
+    rewind: Scancode,
 }
 
 pub enum State {
@@ -16,48 +21,72 @@ pub enum State {
     Decrease,
     Debug,
     Reset,
+    Mute,
+    Save,
+    Load,
+    NextSlot,
+}
+
+// SDL scancode for a key name from the keymap
+fn scancode(name: &str) -> Result<Scancode, String> {
+    Scancode::from_name(name).ok_or(format!("Unknown key name: {}", name))
 }
 
 impl Keypad {
-    pub fn new(sdl_context: &Sdl) -> Self {
-        Keypad {
-            pump: sdl_context.event_pump().unwrap(),
+    pub fn new(sdl_context: &Sdl, keymap: &Keymap) -> Result<Self, String> {
+        if let Some(clash) = keymap.clash() {
+            return Err(clash);
+        }
+
+        let mut keys = [Scancode::X; 16];
+        for (key, name) in keys.iter_mut().zip(&keymap.keys) {
+            *key = scancode(name)?;
         }
+        let mut hotkeys = Vec::new();
+        for (&hotkey, name) in &keymap.hotkeys {
+            hotkeys.push((scancode(name)?, hotkey));
+        }
+
+        Ok(Keypad {
+            pump: sdl_context.event_pump().unwrap(),
+            keys,
+            rewind: scancode(&keymap.hotkeys[&Hotkey::Rewind])?,
+            hotkeys,
+        })
+    }
+
+    // Rewind runs for as long as its key (Backspace) is held down
+    pub fn rewind_held(&self) -> bool {
+        KeyboardState::new(&self.pump).is_scancode_pressed(self.rewind)
     }
 
     // Poll for scancodes
     pub fn key_press(&mut self, key: &mut [u8; 16]) -> State {
         for event in self.pump.poll_iter() {
-            return match event {
-                Event::Quit { .. } | Event::KeyDown {
-                    keycode: Some(Keycode::X), .. } => State::Exit,
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => State::Exit,
-                Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => State::Increase,
-                Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => State::Decrease,
-                Event::KeyDown { keycode: Some(Keycode::F12), .. } => State::Debug,
-                Event::KeyDown { keycode: Some(Keycode::F3), .. } => State::Reset,
-                _ => State::Continue,
+            let scancode = match event {
+                Event::Quit { .. } => return State::Exit,
+                Event::KeyDown { scancode: Some(scancode), .. } => scancode,
+                _ => continue,
             };
+            match self.hotkeys.iter().find(|&&(s, _)| s == scancode).map(|&(_, hotkey)| hotkey) {
+                Some(Hotkey::Exit) => return State::Exit,
+                Some(Hotkey::Faster) => return State::Increase,
+                Some(Hotkey::Slower) => return State::Decrease,
+                Some(Hotkey::Debug) => return State::Debug,
+                Some(Hotkey::Reset) => return State::Reset,
+                Some(Hotkey::Mute) => return State::Mute,
+                Some(Hotkey::Save) => return State::Save,
+                Some(Hotkey::NextSlot) => return State::NextSlot,
+                Some(Hotkey::Load) => return State::Load,
+                Some(Hotkey::Rewind) | None => {}
+            }
         }
 
-        // Keypad is hex values 0-9 A-F
-        let key_state = KeyboardState::new(&mut self.pump);
-        key[0x0] = key_state.is_scancode_pressed(Scancode::Num0) as u8;
-        key[0x1] = key_state.is_scancode_pressed(Scancode::Num1) as u8;
-        key[0x2] = key_state.is_scancode_pressed(Scancode::Num2) as u8;
-        key[0x3] = key_state.is_scancode_pressed(Scancode::Num3) as u8;
-        key[0x4] = key_state.is_scancode_pressed(Scancode::Num4) as u8;
-        key[0x5] = key_state.is_scancode_pressed(Scancode::Num5) as u8;
-        key[0x6] = key_state.is_scancode_pressed(Scancode::Num6) as u8;
-        key[0x7] = key_state.is_scancode_pressed(Scancode::Num7) as u8;
-        key[0x8] = key_state.is_scancode_pressed(Scancode::Num8) as u8;
-        key[0x9] = key_state.is_scancode_pressed(Scancode::Num9) as u8;
-        key[0xA] = key_state.is_scancode_pressed(Scancode::A) as u8;
-        key[0xB] = key_state.is_scancode_pressed(Scancode::B) as u8;
-        key[0xC] = key_state.is_scancode_pressed(Scancode::C) as u8;
-        key[0xD] = key_state.is_scancode_pressed(Scancode::D) as u8;
-        key[0xE] = key_state.is_scancode_pressed(Scancode::E) as u8;
-        key[0xF] = key_state.is_scancode_pressed(Scancode::F) as u8;
+        // Hex keys 0-F from the keymap
+        let key_state = KeyboardState::new(&self.pump);
+        for (down, &scancode) in key.iter_mut().zip(&self.keys) {
+            *down = key_state.is_scancode_pressed(scancode) as u8;
+        }
 
         State::Continue
     }
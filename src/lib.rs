@@ -0,0 +1,18 @@
+// Headless CHIP-8 & SuperChip interpreter core.
+// Front ends (SDL2, test harnesses, tools) drive `cpu::Cpu` and read back its frame buffer.
+pub mod asm;
+pub mod config;
+pub mod cpu;
+pub mod database;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod headless;
+pub mod keymap;
+pub mod movie;
+pub mod options;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod state;
+pub mod video;
@@ -1,47 +1,215 @@
-extern crate rand;
 extern crate sdl2;
 
 use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::process;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
-mod cpu;
+use chip8::asm;
+use chip8::config::Config;
+use chip8::cpu;
+use chip8::debugger::Debugger;
+use chip8::error::Chip8Error;
+use chip8::headless;
+use chip8::movie::Movie;
+use chip8::options::Options;
+use chip8::rewind::Rewind;
+use chip8::video::MemoryVideo;
+
+mod audio;
 mod display;
 mod keypad;
 
+// Read debugger commands on a separate thread so the SDL window stays live while we wait
+fn spawn_prompt() -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
 #[allow(unused_variables)]
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("[Path to rom]");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n\n{}", e, Options::usage());
+            process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", Options::usage());
         return;
     }
-    let bin = &args[1];
+    let bin = match options.rom.clone() {
+        Some(bin) => bin,
+        None => {
+            println!("{}", Options::usage());
+            process::exit(2);
+        }
+    };
+    let config = match Config::load(options.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(2);
+        }
+    };
     let mut cpu = cpu::Cpu::new();
 
+    // The platform decides how large a ROM may be, so a preset picked on the command line or for the file name
+    // applies before loading. Known ROMs then pick their quirks & speed from the ROM database.
+    // Assembly source is assembled in memory first.
+    let named = config.options(&bin, "", None).ok().and_then(|defaults| defaults.preset);
+    if let Some(quirks) = options.preset.or(named) {
+        cpu.quirks = quirks;
+    }
+    let loaded = if asm::is_source(&bin) { cpu.load_source(&bin) } else { cpu.load_bin(&bin) };
+    match loaded {
+        Ok(()) => {}
+        Err(Chip8Error::Asm(e)) => {
+            println!("{}:{}: {}", bin, e.line, e.message);
+            process::exit(1);
+        }
+        Err(e) => {
+            println!("{}: {}", bin, e);
+            process::exit(1);
+        }
+    }
+
+    // Top level config settings fill in what the database leaves unset, the ROM's config section overrides the
+    // database & flags on the command line override everything
+    let options = match config.options(&bin, &cpu.rom_hash, cpu.rom_info.as_ref()) {
+        Ok(defaults) => options.or(defaults),
+        Err(e) => {
+            println!("{}", e);
+            process::exit(2);
+        }
+    };
+    options.apply(&mut cpu);
+
+    // Playing a movie back restores the seed & quirks it was recorded with
+    let mut playback = match options.play {
+        Some(ref path) => match Movie::load(path) {
+            Ok(movie) => {
+                movie.start(&mut cpu);
+                Some(movie)
+            }
+            Err(e) => {
+                println!("{}: {}", path, e);
+                process::exit(2);
+            }
+        },
+        None => None,
+    };
+    let mut recording = options.record.as_ref().map(|_| Movie::new(&cpu));
+    let mut frame = 0;
+
+    // A fixed seed makes runs reproducible, print it so bug reports can include it
+    println!("RNG seed: {}", cpu.seed);
+
+    // No window, run the frames flat out & print the final screen
+    if options.headless {
+        let frames = match options.frames {
+            Some(frames) => frames,
+            None => {
+                println!("--headless needs --frames");
+                process::exit(2);
+            }
+        };
+        let mut video = MemoryVideo::keeping(1);
+        let result = headless::run_frames(&mut cpu, frames, &mut video);
+        print!("{}", headless::screen(video.last().unwrap_or(&Default::default())));
+        if let Err(e) = result {
+            println!("{} ({})", e, cpu.location(cpu.pc()));
+            process::exit(1);
+        }
+        return;
+    }
+
     // SDL2 context
     let sdl_context = sdl2::init().expect("sdl2 init failed in main");
     let mut timer = sdl_context.timer().expect("sdl context timer failed");
 
-    // Load rom
-    cpu.load_bin(bin);
-    let mut keypad = keypad::Keypad::new(&sdl_context);
-    let mut display = display::Display::new(&sdl_context);
+    let scale = options.scale.unwrap_or(display::SCALE_FACTOR);
+    let keymap = options.keymap();
+    let mut keypad = match keypad::Keypad::new(&sdl_context, &keymap) {
+        Ok(keypad) => keypad,
+        Err(e) => {
+            println!("Keymap: {}", e);
+            process::exit(2);
+        }
+    };
+    let mut display = display::Display::new(&sdl_context, scale, options.fullscreen.unwrap_or(false));
+    let frequency = options.frequency.unwrap_or(audio::FREQUENCY);
+    let volume = options.volume.unwrap_or(audio::VOLUME);
+    let mut beeper = audio::Beeper::new(&sdl_context, frequency, volume);
+    beeper.muted = options.mute.unwrap_or(false);
+
+    if let Some(ref info) = cpu.rom_info {
+        if let Some(palette) = info.palette {
+            display.palette = palette;
+        }
+        for (action, key) in &info.keys {
+            println!("Key {:X} ({}): {}", key, keymap.keys[*key as usize & 0xF], action);
+        }
+    }
+    if let Some(palette) = options.palette {
+        display.palette = palette;
+    }
+
+    // Save state slot 0-9, stored next to the rom as <rom>.state<slot>
+    let mut slot = 0;
+
+    // Last 30 seconds of frames for rewinding
+    let mut rewind = Rewind::new(60 * 30);
+
+    // Debugger commands come in on stdin, F12 breaks in
+    let mut debugger = Debugger::new();
+    let commands = spawn_prompt();
+    for addr in &options.breakpoints {
+        debugger.command(&format!("b {}", addr), &mut cpu);
+    }
+    if options.paused {
+        debugger.pause(&cpu);
+    }
+
+    // Set when an instruction fails with the Halt policy, until reset or a state is loaded
+    let mut halted = false;
 
     // Frame timing
     let interval = 1_000 / 60;
     let mut before = timer.ticks();
-    let mut last_second = timer.ticks();
-    let mut fps = 0u16;
+
+    // Movie playback feeds the keypad, real key presses only drive the hotkeys
+    let mut ignored_keys = [0; 16];
 
     'run: loop {
-        match keypad.key_press(&mut cpu.keypad) {
+        // --frames quits once that many frames have run
+        if options.frames == Some(frame as u32) {
+            break 'run;
+        }
+
+        let state = if playback.is_some() {
+            keypad.key_press(&mut ignored_keys)
+        } else {
+            keypad.key_press(&mut cpu.keypad)
+        };
+
+        match state {
             keypad::State::Exit => break 'run,
             keypad::State::Continue => {}
 
-            // TODO Enable & disable debug mode with the same key..
-            keypad::State::Debug => {
-                cpu.mode.debug = true != cpu.mode.debug;
-                println!("Debug:{}", cpu.mode.debug as bool);
-            }
+            keypad::State::Debug => debugger.toggle(&cpu),
             keypad::State::Increase => {
                 cpu.speed = cpu.speed.wrapping_add(1);
                 println!("Speed: {}", cpu.speed);
@@ -52,27 +220,85 @@ fn main() {
             }
             keypad::State::Reset => {
                 cpu.reset();
+                rewind.clear();
+                halted = false;
+            }
+            keypad::State::Mute => beeper.toggle_mute(),
+            keypad::State::NextSlot => {
+                slot = (slot + 1) % 10;
+                println!("State slot: {}", slot);
+            }
+            keypad::State::Save => {
+                let path = format!("{}.state{}", bin, slot);
+                match fs::write(&path, cpu.save_state()) {
+                    Ok(()) => println!("Saved state to {}", path),
+                    Err(e) => println!("Failed to save {}: {}", path, e),
+                }
+            }
+            keypad::State::Load => {
+                let path = format!("{}.state{}", bin, slot);
+                match fs::read(&path).and_then(|data| cpu.load_state(&data)) {
+                    Ok(()) => {
+                        println!("Loaded state from {}", path);
+                        rewind.clear();
+                        halted = false;
+                    }
+                    Err(e) => println!("Failed to load {}: {}", path, e),
+                }
+            }
+        }
+        while let Ok(line) = commands.try_recv() {
+            debugger.command(&line, &mut cpu);
+        }
+
+        // Execute & decode speed opcodes every frame, or run time backwards one frame at a time.
+        // Rewinding would desync movies so it's off while recording or playing back.
+        // While the debugger is paused the machine is frozen, only the window is kept alive.
+        let rewinding = playback.is_none() && recording.is_none() && keypad.rewind_held();
+        let running = !rewinding && !debugger.paused && !halted;
+        if rewinding {
+            rewind.rewind(&mut cpu);
+        } else if running {
+            if let Some(ref movie) = playback {
+                if !movie.play(frame, &mut cpu) {
+                    println!("Movie finished after {} frames", frame);
+                    playback = None;
+                }
+            }
+            if let Some(ref mut movie) = recording {
+                movie.record(&cpu);
+            }
+            frame += 1;
+
+            let speed = cpu.speed;
+            if let Err(e) = debugger.run(&mut cpu, speed) {
+                println!("Halted: {} ({})", e, cpu.location(cpu.pc()));
+                halted = true;
             }
         }
-        // Execute & decode opcodes 2 times for every time we loop
-        cpu.step(cpu.speed, &mut display);
+        cpu.render(&mut display);
 
         // Frame timing
+        let elapsed = timer.ticks() - before;
+        if elapsed < interval {
+            timer.delay(interval - elapsed);
+        }
         let now = timer.ticks();
         let dt = now - before;
-
-        if dt < interval {
-            timer.delay(interval - dt);
-            continue;
-        }
         before = now;
-        fps += 1;
 
-        if now - last_second > 1000 {
-            last_second = now;
-            fps = 0;
+        if running {
+            cpu.update_timers(dt as f32);
+            rewind.push(&cpu);
         }
+        beeper.set_pattern(&cpu.audio_pattern, cpu.pattern_rate());
+        beeper.update(cpu.beeping());
+    }
 
-        cpu.update_timers(dt as f32);
+    if let (Some(path), Some(movie)) = (options.record, recording) {
+        match movie.save(&path) {
+            Ok(()) => println!("Recorded {} frames to {}", movie.frames.len(), path),
+            Err(e) => println!("Failed to save movie {}: {}", path, e),
+        }
     }
 }
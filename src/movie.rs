@@ -0,0 +1,142 @@
+// Input movies: the keypad state & speed of every frame, plus what is needed to start
+// the run identically (RNG seed, quirks & ROM hash). File layout: "C8MV" magic, u16 version,
+// ROM hash terminated by '\n', u64 seed, 7 quirk bytes, u16 stack depth (0 for unlimited),
+// u64 memory size, then (u16 key bitmask, u16 speed) per frame.
+use std::fs;
+use std::io;
+
+use crate::cpu::Cpu;
+use crate::quirks::Quirks;
+use crate::state::{self, StateReader, StateWriter};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+const VERSION: u16 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovieFrame {
+    pub keys: u16, // Bit n set when key n is down
+    pub speed: u16,
+}
+
+pub struct Movie {
+    pub rom_hash: String,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    // Start recording from the Cpu's current ROM, seed & quirks
+    pub fn new(cpu: &Cpu) -> Movie {
+        Movie {
+            rom_hash: cpu.rom_hash.clone(),
+            seed: cpu.seed,
+            quirks: cpu.quirks,
+            frames: Vec::new(),
+        }
+    }
+
+    // Put a freshly loaded Cpu in the state the recording started from
+    pub fn start(&self, cpu: &mut Cpu) {
+        if cpu.rom_hash != self.rom_hash {
+            println!("Warning: movie was recorded with a different ROM");
+        }
+        cpu.seed_rng(self.seed);
+        cpu.quirks = self.quirks;
+    }
+
+    // Call once per frame after the keypad has been read
+    pub fn record(&mut self, cpu: &Cpu) {
+        let keys = cpu
+            .keypad
+            .iter()
+            .enumerate()
+            .fold(0, |keys, (i, &k)| if k != 0 { keys | 1 << i } else { keys });
+
+        self.frames.push(MovieFrame {
+            keys,
+            speed: cpu.speed,
+        });
+    }
+
+    // Feed frame n to the Cpu in place of real input, false once the movie has ended
+    pub fn play(&self, frame: usize, cpu: &mut Cpu) -> bool {
+        let frame = match self.frames.get(frame) {
+            Some(frame) => frame,
+            None => return false,
+        };
+
+        for (i, key) in cpu.keypad.iter_mut().enumerate() {
+            *key = (frame.keys >> i & 1) as u8;
+        }
+        cpu.speed = frame.speed;
+        true
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = StateWriter::with_magic(MAGIC, VERSION);
+
+        w.bytes(self.rom_hash.as_bytes());
+        w.u8(b'\n');
+        w.u64(self.seed);
+        w.bool(self.quirks.shift);
+        w.bool(self.quirks.load_store);
+        w.bool(self.quirks.jump);
+        w.bool(self.quirks.clip);
+        w.bool(self.quirks.vf_reset);
+        w.bool(self.quirks.display_wait);
+        w.bool(self.quirks.half_scroll);
+        w.u16(self.quirks.stack_depth.unwrap_or(0));
+        w.u64(self.quirks.memory_size as u64);
+        for frame in &self.frames {
+            w.u16(frame.keys);
+            w.u16(frame.speed);
+        }
+        w.finish()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Movie> {
+        let mut r = StateReader::with_magic(data, MAGIC, VERSION)?;
+
+        let mut hash = Vec::new();
+        loop {
+            match r.u8()? {
+                b'\n' => break,
+                c => hash.push(c),
+            }
+        }
+
+        let mut movie = Movie {
+            rom_hash: String::from_utf8_lossy(&hash).into_owned(),
+            seed: r.u64()?,
+            quirks: Quirks {
+                shift: r.bool()?,
+                load_store: r.bool()?,
+                jump: r.bool()?,
+                clip: r.bool()?,
+                vf_reset: r.bool()?,
+                display_wait: r.bool()?,
+                half_scroll: r.bool()?,
+                stack_depth: Some(r.u16()?).filter(|&depth| depth > 0),
+                memory_size: state::memory_size(r.u64()?)?,
+            },
+            frames: Vec::new(),
+        };
+
+        while !r.is_empty() {
+            movie.frames.push(MovieFrame {
+                keys: r.u16()?,
+                speed: r.u16()?,
+            });
+        }
+        Ok(movie)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &str) -> io::Result<Movie> {
+        Movie::from_bytes(&fs::read(path)?)
+    }
+}
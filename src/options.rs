@@ -0,0 +1,232 @@
+// Command line options for the chip8 front end
+use std::collections::BTreeMap;
+
+use crate::config;
+use crate::cpu::{Cpu, MemoryPolicy};
+use crate::database;
+use crate::error::{ErrorPolicy, POLICIES};
+use crate::keymap::{Hotkey, Keymap, KEYMAPS};
+use crate::quirks::{Quirks, PRESETS};
+use crate::video::Palette;
+
+pub const USAGE: &str = "\
+Usage: chip8 [options] ROM
+
+ROM is a binary (.ch8, .sc8, ...) or CHIPPER (.src) / Octo (.8o) source, which is assembled first.
+
+Machine:
+  --preset NAME         Quirks preset: PRESETS, picked from the ROM database by default
+  --platform NAME       Same as --preset
+  --cycles N            Instructions per frame, picked from the ROM database by default
+  --seed N              RNG seed for CXNN, to reproduce a run
+  --stack N|unlimited   Stack depth, 12 for chip8 & 16 otherwise
+  --memory wrap|trap    What I based accesses past the end of memory do (default wrap)
+  --on-error POLICY     What failing instructions do: POLICIES (default halt)
+
+Display & sound:
+  --scale N             Window scale, a SCHIP pixel is N x N (default 10)
+  --palette COLORS      Comma separated hex colours: background,plane 1[,plane 2,both planes]
+  --fullscreen          Start fullscreen
+  --keymap NAME         Keypad layout: KEYMAPS (default vip, 1234/QWER/ASDF/ZXCV)
+  --mute                Start with the sound muted
+  --volume N            Beeper volume, 0 to 1 (default 0.25)
+  --frequency HZ        Beeper tone (default 440)
+
+Running:
+  --headless            Run without a window & print the screen at the end, needs --frames
+  --frames N            Quit after N frames
+  --record FILE         Record input to a movie
+  --play FILE           Play back a movie recorded with --record
+
+Debugging:
+  --paused              Start paused in the debugger
+  --break ADDR          Set a breakpoint, an address or FILE:line (repeatable)
+  --trace               Print every instruction executed
+  --config FILE         Read settings from FILE instead of CONFIG
+  -h, --help            Show this help";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    pub rom: Option<String>,
+    pub preset: Option<Quirks>,
+    pub cycles: Option<u16>,
+    pub seed: Option<u64>,
+    pub stack_depth: Option<Option<u16>>,
+    pub memory: Option<MemoryPolicy>,
+    pub on_error: Option<ErrorPolicy>,
+    pub scale: Option<u32>,
+    pub palette: Option<Palette>,
+    pub fullscreen: Option<bool>, // Left unset so the config file can turn it off for a single ROM
+    pub keymap: Option<Keymap>,
+    pub keys: BTreeMap<u8, String>, // Keypad keys rebound in the config file
+    pub hotkeys: BTreeMap<Hotkey, String>, // Hotkeys rebound in the config file
+    pub mute: Option<bool>,
+    pub volume: Option<f32>,
+    pub frequency: Option<f32>,
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub record: Option<String>,
+    pub play: Option<String>,
+    pub paused: bool,
+    pub breakpoints: Vec<String>, // Passed to the debugger's break command once the ROM is loaded
+    pub trace: bool,
+    pub config: Option<String>,
+    pub help: bool,
+}
+
+impl Options {
+    // Parse the arguments after the program name, `--name value` & `--name=value` are both accepted
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                if options.rom.is_some() {
+                    return Err(format!("Unexpected argument: {}", arg));
+                }
+                options.rom = Some(arg.clone());
+                continue;
+            }
+
+            let (name, inline) = match arg.find('=') {
+                Some(pos) => (&arg[..pos], Some(arg[pos + 1..].to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = || match inline.clone().or_else(|| args.next().cloned()) {
+                Some(value) => Ok(value),
+                None => Err(format!("{} needs a value", name)),
+            };
+
+            match name {
+                "-h" | "--help" => options.help = true,
+                "--fullscreen" => options.fullscreen = Some(true),
+                "--mute" => options.mute = Some(true),
+                "--headless" => options.headless = true,
+                "--paused" => options.paused = true,
+                "--trace" => options.trace = true,
+                "--preset" | "--platform" => {
+                    let value = value()?;
+                    options.preset = Some(Quirks::from_preset(&value).ok_or(format!("Unknown quirks preset: {}", value))?);
+                }
+                "--cycles" => options.cycles = Some(number(name, &value()?)?),
+                "--seed" => options.seed = Some(number(name, &value()?)?),
+                "--stack" => {
+                    let value = value()?;
+                    options.stack_depth = Some(Quirks::parse_stack_depth(&value).ok_or(format!("Invalid stack depth: {}", value))?);
+                }
+                "--memory" => {
+                    let value = value()?;
+                    options.memory = Some(MemoryPolicy::from_name(&value).ok_or(format!("Unknown memory policy: {}", value))?);
+                }
+                "--on-error" => {
+                    let value = value()?;
+                    options.on_error = Some(ErrorPolicy::from_name(&value).ok_or(format!("Unknown error policy: {}", value))?);
+                }
+                "--scale" => match number(name, &value()?)? {
+                    0 => return Err("--scale must be at least 1".to_string()),
+                    scale => options.scale = Some(scale),
+                },
+                "--keymap" => {
+                    let value = value()?;
+                    options.keymap = Some(Keymap::from_preset(&value).ok_or(format!("Unknown keymap: {}", value))?);
+                }
+                "--palette" => options.palette = Some(palette(&value()?)?),
+                "--volume" => options.volume = Some(number(name, &value()?)?),
+                "--frequency" => options.frequency = Some(number(name, &value()?)?),
+                "--config" => options.config = Some(value()?),
+                "--frames" => options.frames = Some(number(name, &value()?)?),
+                "--record" => options.record = Some(value()?),
+                "--play" => options.play = Some(value()?),
+                "--break" => options.breakpoints.push(value()?),
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    // Help text with the preset & policy names & the config file location filled in
+    pub fn usage() -> String {
+        let config = config::default_path().map_or("the default config".to_string(), |path| path.display().to_string());
+        USAGE
+            .replace("PRESETS", &PRESETS.join(", "))
+            .replace("POLICIES", &POLICIES.join(", "))
+            .replace("KEYMAPS", &KEYMAPS.join(", "))
+            .replace("CONFIG", &config)
+    }
+
+    // Settings given here win, anything left unset comes from defaults (the config file)
+    pub fn or(self, defaults: Options) -> Options {
+        Options {
+            rom: self.rom.or(defaults.rom),
+            preset: self.preset.or(defaults.preset),
+            cycles: self.cycles.or(defaults.cycles),
+            seed: self.seed.or(defaults.seed),
+            stack_depth: self.stack_depth.or(defaults.stack_depth),
+            memory: self.memory.or(defaults.memory),
+            on_error: self.on_error.or(defaults.on_error),
+            scale: self.scale.or(defaults.scale),
+            palette: self.palette.or(defaults.palette),
+            fullscreen: self.fullscreen.or(defaults.fullscreen),
+            keymap: self.keymap.or(defaults.keymap),
+            keys: defaults.keys.into_iter().chain(self.keys).collect(),
+            hotkeys: defaults.hotkeys.into_iter().chain(self.hotkeys).collect(),
+            mute: self.mute.or(defaults.mute),
+            volume: self.volume.or(defaults.volume),
+            frequency: self.frequency.or(defaults.frequency),
+            headless: self.headless || defaults.headless,
+            frames: self.frames.or(defaults.frames),
+            record: self.record.or(defaults.record),
+            play: self.play.or(defaults.play),
+            paused: self.paused || defaults.paused,
+            breakpoints: [defaults.breakpoints, self.breakpoints].concat(),
+            trace: self.trace || defaults.trace,
+            config: self.config.or(defaults.config),
+            help: self.help || defaults.help,
+        }
+    }
+
+    // The keymap preset with any rebound keys on top
+    pub fn keymap(&self) -> Keymap {
+        let mut keymap = self.keymap.clone().unwrap_or_default();
+        for (&key, name) in &self.keys {
+            keymap.keys[key as usize] = name.clone();
+        }
+        for (&hotkey, name) in &self.hotkeys {
+            keymap.hotkeys.insert(hotkey, name.clone());
+        }
+        keymap
+    }
+
+    // Machine settings override the ROM database, the stack & memory settings apply on top of the preset
+    pub fn apply(&self, cpu: &mut Cpu) {
+        if let Some(quirks) = self.preset {
+            cpu.quirks = quirks;
+        }
+        if let Some(depth) = self.stack_depth {
+            cpu.quirks.stack_depth = depth;
+        }
+        if let Some(cycles) = self.cycles {
+            cpu.speed = cycles;
+        }
+        if let Some(seed) = self.seed {
+            cpu.seed_rng(seed);
+        }
+        if let Some(policy) = self.memory {
+            cpu.memory_policy = policy;
+        }
+        if let Some(policy) = self.on_error {
+            cpu.on_error = policy;
+        }
+        cpu.mode.debug = self.trace;
+    }
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid number for {}: {}", name, value))
+}
+
+fn palette(value: &str) -> Result<Palette, String> {
+    let colors: Vec<String> = value.split(',').map(|c| c.trim().to_string()).collect();
+    database::parse_palette(&colors).ok_or(format!("Invalid palette: {}", value))
+}
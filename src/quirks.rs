@@ -0,0 +1,161 @@
+// Behaviour differences between CHIP-8 interpreters.
+// See https://chip-8.github.io/extensions/ & Timendus' quirks test for what each platform does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    pub shift: bool,              // 8XY6 & 8XYE shift Vx in place, ignoring Vy
+    pub load_store: bool,         // FX55 & FX65 leave I incremented by X + 1
+    pub jump: bool,               // BXNN jumps to XNN + Vx instead of BNNN jumping to NNN + V0
+    pub clip: bool,               // DXYN clips sprites at the screen edges instead of wrapping them
+    pub vf_reset: bool,           // 8XY1, 8XY2 & 8XY3 reset VF to 0
+    pub display_wait: bool,       // DXYN waits for the vertical blank, at most one sprite per frame
+    pub half_scroll: bool,        // 00CN, 00DN, 00FB & 00FC scroll half as far in lores, XO-CHIP scrolls lores pixels
+    pub stack_depth: Option<u16>, // Nested CALLs before the stack overflows, None for unlimited
+    pub memory_size: usize,       // Address space reachable through I, 4 KiB or 64 KiB for XO-CHIP
+}
+
+// Named presets selectable from the command line
+pub const PRESETS: [&str; 6] = ["default", "chip8", "chip48", "schip10", "schip11", "xochip"];
+
+impl Default for Quirks {
+    // The behaviour this interpreter has always had
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            load_store: false,
+            jump: false,
+            clip: false,
+            vf_reset: false,
+            display_wait: false,
+            half_scroll: true,
+            stack_depth: Some(16),
+            memory_size: 0x1000,
+        }
+    }
+}
+
+impl Quirks {
+    // Original COSMAC VIP interpreter, the stack has room for 12 return addresses
+    pub fn chip8() -> Quirks {
+        Quirks {
+            shift: false,
+            load_store: true,
+            jump: false,
+            clip: true,
+            vf_reset: true,
+            display_wait: true,
+            half_scroll: true,
+            stack_depth: Some(12),
+            memory_size: 0x1000,
+        }
+    }
+
+    // HP48 CHIP-48
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift: true,
+            load_store: true,
+            jump: true,
+            clip: true,
+            vf_reset: false,
+            display_wait: false,
+            half_scroll: true,
+            stack_depth: Some(16),
+            memory_size: 0x1000,
+        }
+    }
+
+    // HP48 SuperChip 1.0
+    pub fn schip10() -> Quirks {
+        Quirks {
+            load_store: true,
+            ..Quirks::schip11()
+        }
+    }
+
+    // HP48 SuperChip 1.1
+    pub fn schip11() -> Quirks {
+        Quirks {
+            shift: true,
+            load_store: false,
+            jump: true,
+            clip: true,
+            vf_reset: false,
+            display_wait: false,
+            half_scroll: true,
+            stack_depth: Some(16),
+            memory_size: 0x1000,
+        }
+    }
+
+    // Octo's XO-CHIP
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift: false,
+            load_store: true,
+            jump: false,
+            clip: false,
+            vf_reset: false,
+            display_wait: false,
+            half_scroll: false,
+            stack_depth: Some(16),
+            memory_size: 0x10000,
+        }
+    }
+
+    // Platform ids used by the community chip-8-database (platforms.json)
+    pub fn from_platform(id: &str) -> Option<Quirks> {
+        match id {
+            "originalChip8" | "hybridVIP" => Some(Quirks::chip8()),
+            "modernChip8" => Some(Quirks {
+                shift: false,
+                load_store: true,
+                jump: false,
+                clip: true,
+                vf_reset: false,
+                display_wait: false,
+                half_scroll: true,
+                stack_depth: Some(16),
+                memory_size: 0x1000,
+            }),
+            "chip48" => Some(Quirks::chip48()),
+            "superchip1" => Some(Quirks::schip10()),
+            "superchip" => Some(Quirks::schip11()),
+            "xochip" => Some(Quirks::xochip()),
+            _ => None,
+        }
+    }
+
+    // Apply a single chip-8-database quirk override (quirkyPlatforms), unknown names are ignored
+    pub fn set(&mut self, name: &str, value: bool) {
+        match name {
+            "shift" => self.shift = value,
+            "memoryLeaveIUnchanged" => self.load_store = !value,
+            "memoryIncrementByX" => self.load_store = value,
+            "jump" => self.jump = value,
+            "wrap" => self.clip = !value,
+            "logic" => self.vf_reset = value,
+            "vblank" => self.display_wait = value,
+            _ => {}
+        }
+    }
+
+    pub fn from_preset(name: &str) -> Option<Quirks> {
+        match name.to_lowercase().as_str() {
+            "default" => Some(Quirks::default()),
+            "chip8" | "chip-8" | "vip" => Some(Quirks::chip8()),
+            "chip48" | "chip-48" => Some(Quirks::chip48()),
+            "schip10" | "schip1.0" => Some(Quirks::schip10()),
+            "schip11" | "schip1.1" | "schip" => Some(Quirks::schip11()),
+            "xochip" | "xo-chip" => Some(Quirks::xochip()),
+            _ => None,
+        }
+    }
+
+    // Stack depth given on the command line: a number of entries or "unlimited"
+    pub fn parse_stack_depth(text: &str) -> Option<Option<u16>> {
+        match text {
+            "unlimited" => Some(None),
+            _ => text.parse().ok().filter(|&depth| depth > 0).map(Some),
+        }
+    }
+}
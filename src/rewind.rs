@@ -0,0 +1,110 @@
+// Rewind ring buffer. Each frame's save state is stored as the run-length encoded XOR
+// against the following frame, so a frame where little changed only costs a few bytes.
+// Save states grow & shrink with the stack, a frame ending at another call depth is kept whole.
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+
+enum Step {
+    Delta(Vec<u8>),    // XOR against the following snapshot
+    Keyframe(Vec<u8>), // Whole snapshot, its length differs from the following one
+}
+
+pub struct Rewind {
+    deltas: VecDeque<Step>,    // Steps back between consecutive snapshots, newest last
+    current: Vec<u8>,          // Newest snapshot
+    capacity: usize,           // Frames kept
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind {
+            deltas: VecDeque::with_capacity(capacity),
+            current: Vec::new(),
+            capacity,
+        }
+    }
+
+    // Record the state at the end of a frame
+    pub fn push(&mut self, cpu: &Cpu) {
+        let snapshot = cpu.save_state();
+
+        if !self.current.is_empty() {
+            let step = if snapshot.len() == self.current.len() {
+                Step::Delta(encode(&self.current, &snapshot))
+            } else {
+                Step::Keyframe(self.current.clone())
+            };
+            self.deltas.push_back(step);
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.current = snapshot;
+    }
+
+    // Step the Cpu back one frame, false once the buffer is exhausted
+    pub fn rewind(&mut self, cpu: &mut Cpu) -> bool {
+        match self.deltas.pop_back() {
+            Some(Step::Delta(delta)) => apply(&mut self.current, &delta),
+            Some(Step::Keyframe(snapshot)) => self.current = snapshot,
+            None => return false,
+        }
+        cpu.load_state(&self.current).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.current.clear();
+    }
+}
+
+// Runs of (u16 unchanged bytes, u16 changed bytes, changed bytes XORed)
+fn encode(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+
+    while pos < new.len() {
+        let start = pos;
+        while pos < new.len() && pos - start < 0xFFFF && old[pos] == new[pos] {
+            pos += 1;
+        }
+        let skip = pos - start;
+
+        let start = pos;
+        while pos < new.len() && pos - start < 0xFFFF && old[pos] != new[pos] {
+            pos += 1;
+        }
+
+        out.extend_from_slice(&(skip as u16).to_le_bytes());
+        out.extend_from_slice(&((pos - start) as u16).to_le_bytes());
+        out.extend(old[start..pos].iter().zip(&new[start..pos]).map(|(a, b)| a ^ b));
+    }
+    out
+}
+
+fn apply(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut i = 0;
+
+    while i + 4 <= delta.len() {
+        let skip = u16::from_le_bytes([delta[i], delta[i + 1]]) as usize;
+        let len = u16::from_le_bytes([delta[i + 2], delta[i + 3]]) as usize;
+        i += 4;
+        pos += skip;
+
+        for (byte, x) in state[pos..pos + len].iter_mut().zip(&delta[i..i + len]) {
+            *byte ^= x;
+        }
+        pos += len;
+        i += len;
+    }
+}
@@ -0,0 +1,141 @@
+// Save state file format: "C8ST" magic, u16 version, then the fields written by Cpu::save_state.
+// Multi-byte values are little endian. Bump VERSION whenever the layout changes.
+use std::io;
+
+use crate::cpu::MEMORY_SIZE;
+
+pub const MAGIC: &[u8; 4] = b"C8ST";
+pub const VERSION: u16 = 5;
+
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter::with_magic(MAGIC, VERSION)
+    }
+
+    // Other file formats (e.g. movies) reuse the writer with their own magic & version
+    pub fn with_magic(magic: &[u8; 4], version: u16) -> StateWriter {
+        let mut writer = StateWriter { buf: Vec::new() };
+        writer.bytes(magic);
+        writer.u16(version);
+        writer
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.buf.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, value: f32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> io::Result<StateReader<'a>> {
+        StateReader::with_magic(data, MAGIC, VERSION)
+    }
+
+    pub fn with_magic(data: &'a [u8], magic: &[u8; 4], version: u16) -> io::Result<StateReader<'a>> {
+        let mut reader = StateReader { data, pos: 0 };
+
+        let mut found = [0; 4];
+        reader.bytes(&mut found)?;
+        if &found != magic {
+            return Err(invalid("Unrecognised file type"));
+        }
+
+        let found = reader.u16()?;
+        if found != version {
+            return Err(invalid(&format!("Unsupported file version {}", found)));
+        }
+        Ok(reader)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        let mut buf = [0; 1];
+        self.bytes(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    pub fn bool(&mut self) -> io::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        let mut buf = [0; 2];
+        self.bytes(&mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        self.bytes(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn f32(&mut self) -> io::Result<f32> {
+        let mut buf = [0; 4];
+        self.bytes(&mut buf)?;
+        Ok(f32::from_le_bytes(buf))
+    }
+
+    pub fn bytes(&mut self, out: &mut [u8]) -> io::Result<()> {
+        let end = self.pos + out.len();
+        if end > self.data.len() {
+            return Err(invalid("Save state is truncated"));
+        }
+        out.copy_from_slice(&self.data[self.pos..end]);
+        self.pos = end;
+        Ok(())
+    }
+}
+
+pub fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Quirks::memory_size read back from a file, it is used as an address mask so must be a power of two
+pub fn memory_size(value: u64) -> io::Result<usize> {
+    match value as usize {
+        size if size.is_power_of_two() && size <= MEMORY_SIZE => Ok(size),
+        _ => Err(invalid("Bad memory size")),
+    }
+}
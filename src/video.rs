@@ -0,0 +1,73 @@
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+
+// The frame buffer is always SCHIP sized, CHIP-8 games only use the upper left 64x32.
+// Each pixel holds a bitplane mask: bit 0 is plane 1, bit 1 is the XO-CHIP second plane.
+pub type Pixels = [[u8; WIDTH]; HEIGHT];
+
+// RGB colours indexed by a pixel's bitplane mask: background, plane 1, plane 2, both planes
+pub type Palette = [(u8, u8, u8); 4];
+
+pub const DEFAULT_PALETTE: Palette = [(69, 133, 149), (251, 241, 199), (214, 93, 14), (40, 40, 40)];
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DisplayMode {
+    Normal,
+    Extended,
+}
+
+// Rendering backend the front end hands finished frames to.
+// Implement this to add a new renderer (SDL2 window, terminal, image dump, test capture).
+pub trait Video {
+    fn draw(&mut self, pixels: &Pixels, mode: DisplayMode);
+}
+
+#[derive(Clone)]
+pub struct Frame {
+    pub pixels: Pixels,
+    pub mode: DisplayMode,
+}
+
+// A blank lores screen, what is shown before anything is drawn
+impl Default for Frame {
+    fn default() -> Frame {
+        Frame {
+            pixels: [[0; WIDTH]; HEIGHT],
+            mode: DisplayMode::Normal,
+        }
+    }
+}
+
+// In-memory backend that records every frame it is handed, used by the headless runner & tests
+#[derive(Default)]
+pub struct MemoryVideo {
+    pub frames: Vec<Frame>,
+    pub keep: Option<usize>, // Only keep the last N frames, long headless runs would use a lot of memory otherwise
+}
+
+impl MemoryVideo {
+    pub fn new() -> MemoryVideo {
+        MemoryVideo { frames: Vec::new(), keep: None }
+    }
+
+    pub fn keeping(frames: usize) -> MemoryVideo {
+        MemoryVideo { frames: Vec::new(), keep: Some(frames) }
+    }
+
+    pub fn last(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+}
+
+impl Video for MemoryVideo {
+    fn draw(&mut self, pixels: &Pixels, mode: DisplayMode) {
+        self.frames.push(Frame {
+            pixels: *pixels,
+            mode,
+        });
+        if let Some(keep) = self.keep {
+            let excess = self.frames.len().saturating_sub(keep);
+            self.frames.drain(..excess);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// Config file parsing & merging with per-ROM sections & command line flags
+use chip8::config::Config;
+use chip8::cpu::Cpu;
+use chip8::options::Options;
+use chip8::quirks::Quirks;
+
+const CONFIG: &str = r##"
+cycles = 20
+scale = 6
+palette = ["#101010", "#F0F0F0"]
+volume = 0.1
+
+[rom."BRIX.ch8"]
+preset = "chip8"
+cycles = 15
+stack = "unlimited"
+
+[rom."0123456789abcdef0123456789abcdef01234567"]
+fullscreen = true
+stack = 32
+"##;
+
+#[test]
+fn defaults() {
+    let config = Config::parse(CONFIG).unwrap();
+    let options = config.options("roms/PONG", "", None).unwrap();
+    assert_eq!(options.preset, None);
+    assert_eq!(options.cycles, Some(20));
+    assert_eq!(options.scale, Some(6));
+    assert_eq!(options.palette.unwrap()[0], (0x10, 0x10, 0x10));
+    assert_eq!(options.volume, Some(0.1));
+}
+
+#[test]
+fn rom_section_by_name() {
+    let config = Config::parse(CONFIG).unwrap();
+    let options = config.options("roms/BRIX.ch8", "", None).unwrap();
+    assert_eq!(options.preset, Some(Quirks::chip8()));
+    assert_eq!(options.cycles, Some(15));
+    assert_eq!(options.stack_depth, Some(None));
+    assert_eq!(options.scale, Some(6));
+}
+
+#[test]
+fn rom_section_by_hash() {
+    let config = Config::parse(CONFIG).unwrap();
+    let options = config.options("game.ch8", "0123456789abcdef0123456789abcdef01234567", None).unwrap();
+    assert_eq!(options.fullscreen, Some(true));
+    assert_eq!(options.stack_depth, Some(Some(32)));
+    assert_eq!(options.cycles, Some(20));
+}
+
+#[test]
+fn rom_section_turns_settings_off() {
+    let config = Config::parse(
+        r#"
+fullscreen = true
+mute = true
+
+[rom."PONG"]
+fullscreen = false
+mute = false
+"#,
+    )
+    .unwrap();
+    let options = config.options("roms/PONG", "", None).unwrap();
+    assert_eq!((options.fullscreen, options.mute), (Some(false), Some(false)));
+    let options = config.options("roms/BRIX", "", None).unwrap();
+    assert_eq!((options.fullscreen, options.mute), (Some(true), Some(true)));
+}
+
+#[test]
+fn flags_win() {
+    let config = Config::parse(CONFIG).unwrap();
+    let args: Vec<String> = ["--cycles", "9", "roms/BRIX.ch8"].iter().map(|s| s.to_string()).collect();
+    let options = Options::parse(&args).unwrap().or(config.options("roms/BRIX.ch8", "", None).unwrap());
+    assert_eq!(options.cycles, Some(9));
+    assert_eq!(options.preset, Some(Quirks::chip8()));
+    assert_eq!(options.rom.as_deref(), Some("roms/BRIX.ch8"));
+}
+
+#[test]
+fn database_beats_top_level_settings() {
+    let mut cpu = Cpu::new();
+    cpu.load_bin(concat!(env!("CARGO_MANIFEST_DIR"), "/roms/BRIX.ch8")).unwrap();
+    let config = Config::parse("cycles = 20\npreset = \"schip11\"\nstack = 64\nscale = 6").unwrap();
+    let options = config.options("roms/BRIX.ch8", &cpu.rom_hash, cpu.rom_info.as_ref()).unwrap();
+    assert_eq!((options.cycles, options.preset, options.stack_depth), (None, None, None));
+    assert_eq!(options.scale, Some(6));
+    options.apply(&mut cpu);
+    assert_eq!(cpu.speed, 30);
+    assert_eq!(cpu.quirks, Quirks::from_platform("chip48").unwrap());
+
+    // ROMs the database doesn't know still get them
+    let options = config.options("game.ch8", "", None).unwrap();
+    assert_eq!(options.cycles, Some(20));
+    assert_eq!(options.stack_depth, Some(Some(64)));
+
+    // A [rom] section overrides the database
+    let config = Config::parse("cycles = 20\n[rom.\"BRIX.ch8\"]\ncycles = 40").unwrap();
+    config.options("roms/BRIX.ch8", &cpu.rom_hash, cpu.rom_info.as_ref()).unwrap().apply(&mut cpu);
+    assert_eq!(cpu.speed, 40);
+}
+
+#[test]
+fn errors() {
+    assert!(Config::parse("speed = 10").is_err());
+    assert!(Config::parse("[rom.\"BRIX.ch8\"]\ncolour = 1").is_err());
+    let config = Config::parse("[rom.\"BRIX.ch8\"]\npreset = \"nes\"").unwrap();
+    assert_eq!(config.options("BRIX.ch8", "", None).unwrap_err(), "[rom.\"BRIX.ch8\"] Unknown quirks preset: nes");
+    assert!(Config::load(Some("does-not-exist.toml")).is_err());
+}
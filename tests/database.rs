@@ -0,0 +1,77 @@
+// ROM database lookups, the bundled database/programs.json & the chip-8-database format
+use std::env;
+use std::fs;
+
+use chip8::cpu::{Cpu, DEFAULT_SPEED};
+use chip8::database::Database;
+use chip8::quirks::Quirks;
+
+const BUNDLED: &str = include_str!("../database/programs.json");
+
+// Every entry gives its speed instead of relying on the platform default
+#[test]
+fn bundled_roms_have_tickrates() {
+    let programs: serde_json::Value = serde_json::from_str(BUNDLED).unwrap();
+    for program in programs.as_array().unwrap() {
+        for rom in program["roms"].as_object().unwrap().values() {
+            assert!(rom["tickrate"].is_u64(), "{} has no tickrate", rom["file"]);
+            assert!(!rom["platforms"].as_array().unwrap().is_empty(), "{} has no platforms", rom["file"]);
+        }
+    }
+}
+
+#[test]
+fn brix_gets_its_database_speed() {
+    let mut cpu = Cpu::new();
+    cpu.load_bin(concat!(env!("CARGO_MANIFEST_DIR"), "/roms/BRIX.ch8")).unwrap();
+    let info = cpu.rom_info.as_ref().expect("BRIX is in the database");
+    assert_eq!(info.title, "Brix");
+    assert_eq!(info.tickrate, 30);
+    assert_eq!(cpu.speed, 30);
+}
+
+// A ROM file outside the database, in the temp directory
+fn unknown_rom(name: &str, size: usize) -> String {
+    let path = env::temp_dir().join(format!("chip8-{}-{}.ch8", name, std::process::id()));
+    fs::write(&path, vec![0x12; size]).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn unknown_rom_drops_the_previous_entry() {
+    let mut cpu = Cpu::new();
+    cpu.load_bin(concat!(env!("CARGO_MANIFEST_DIR"), "/roms/BRIX.ch8")).unwrap();
+    let path = unknown_rom("unknown", 4);
+    cpu.load_bin(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(cpu.rom_info.is_none());
+    assert_eq!(cpu.quirks, Quirks::default());
+    assert_eq!(cpu.speed, DEFAULT_SPEED);
+}
+
+#[test]
+fn failed_load_changes_nothing() {
+    let mut cpu = Cpu::new();
+    cpu.load_bin(concat!(env!("CARGO_MANIFEST_DIR"), "/roms/BRIX.ch8")).unwrap();
+    let (hash, quirks) = (cpu.rom_hash.clone(), cpu.quirks);
+    let path = unknown_rom("too-large", 0x1000);
+    assert!(cpu.load_bin(&path).is_err());
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(cpu.rom_info.as_ref().unwrap().title, "Brix");
+    assert_eq!((cpu.rom_hash.as_str(), cpu.quirks, cpu.speed), (hash.as_str(), quirks, 30));
+}
+
+#[test]
+fn tickrate_overrides_the_platform_default() {
+    let json = r#"[
+        {"title": "Fast", "roms": {"AA": {"platforms": ["originalChip8"], "tickrate": 200}}},
+        {"title": "Default", "roms": {"bb": {"platforms": ["originalChip8"]}}},
+        {"title": "Unknown platform", "roms": {"cc": {"platforms": ["megachip8"]}}}
+    ]"#;
+    let database = Database::from_json(json).unwrap();
+    assert_eq!(database.lookup("aa").unwrap().tickrate, 200);
+    assert_eq!(database.lookup("BB").unwrap().tickrate, 15);
+    assert!(database.lookup("cc").is_none());
+}
@@ -0,0 +1,179 @@
+// Watchpoints, breakpoints & stepping, driven through Debugger::command & Debugger::run
+use chip8::cpu::Cpu;
+use chip8::debugger::Debugger;
+use chip8::quirks::Quirks;
+
+// CALL 206, LD V0 1, JP 204 / ADD V1 1, ADD V1 1, RET
+const CALL: [u16; 6] = [0x2206, 0x6001, 0x1204, 0x7101, 0x7101, 0x00EE];
+
+fn load(words: &[u16]) -> Cpu {
+    let rom: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&rom).unwrap();
+    cpu
+}
+
+// Run the program once through with a single watch, true when the watch stopped it
+fn stops(watch: &str, words: &[u16]) -> bool {
+    let mut cpu = load(words);
+    let mut debugger = Debugger::new();
+    debugger.command(watch, &mut cpu);
+    debugger.run(&mut cpu, words.len() as u16).unwrap();
+    debugger.paused
+}
+
+#[test]
+fn bcd_writes() {
+    // FX33 writes I to I + 2
+    assert!(stops("w 300-302", &[0xA2FE, 0xF033]));
+    assert!(!stops("w 300-302", &[0xA2FB, 0xF033]));
+    assert!(stops("aw 300", &[0xA2FE, 0xF033]));
+    assert!(!stops("rw 300-302", &[0xA2FE, 0xF033]));
+}
+
+#[test]
+fn register_stores() {
+    // F855 writes V0-V8 to 2F8-300
+    assert!(stops("w 300", &[0xA2F8, 0xF855]));
+    assert!(!stops("w 300", &[0xA2F8, 0xF755]));
+    assert!(!stops("rw 300", &[0xA2F8, 0xF855]));
+}
+
+#[test]
+fn register_loads() {
+    // F365 reads 300-303
+    assert!(stops("rw 303-304", &[0xA300, 0xF365]));
+    assert!(!stops("rw 304-310", &[0xA300, 0xF365]));
+    assert!(!stops("w 300-303", &[0xA300, 0xF365]));
+    assert!(stops("aw 2F0-300", &[0xA300, 0xF365]));
+}
+
+#[test]
+fn sprite_reads() {
+    // D015 reads 5 sprite rows from 300-304
+    assert!(stops("rw 304", &[0xA300, 0xD015]));
+    assert!(!stops("rw 305", &[0xA300, 0xD015]));
+    assert!(!stops("w 300-304", &[0xA300, 0xD015]));
+}
+
+#[test]
+fn memory_watch_stops_after_the_access() {
+    let mut cpu = load(&[0xA300, 0xF033, 0x6001]);
+    let mut debugger = Debugger::new();
+    debugger.command("w 301", &mut cpu);
+    debugger.run(&mut cpu, 10).unwrap();
+    assert!(debugger.paused);
+    assert_eq!(cpu.pc(), 0x204);
+}
+
+#[test]
+fn condition_fires_on_the_transition() {
+    // ADD VA 4, JP 200
+    let mut cpu = load(&[0x7A04, 0x1200]);
+    let mut debugger = Debugger::new();
+    // Numbers are hex, 10 is 16
+    debugger.command("when VA >= 10", &mut cpu);
+    debugger.run(&mut cpu, 100).unwrap();
+    assert!(debugger.paused);
+    assert_eq!(cpu.v()[0xA], 16);
+
+    // Still true, but it already was
+    debugger.command("c", &mut cpu);
+    debugger.run(&mut cpu, 100).unwrap();
+    assert!(!debugger.paused);
+    assert_eq!(cpu.v()[0xA], 216);
+
+    // Around the wrap it turns false & then true again
+    debugger.run(&mut cpu, 100).unwrap();
+    assert!(debugger.paused);
+    assert_eq!(cpu.v()[0xA], 16);
+}
+
+#[test]
+fn register_change() {
+    // LD V1 0 (no change), LD V1 5
+    let mut cpu = load(&[0x6100, 0x6105, 0x6205]);
+    let mut debugger = Debugger::new();
+    debugger.command("when V1", &mut cpu);
+    debugger.run(&mut cpu, 10).unwrap();
+    assert!(debugger.paused);
+    assert_eq!(cpu.pc(), 0x204);
+}
+
+#[test]
+fn breakpoints() {
+    let mut cpu = load(&CALL);
+    let mut debugger = Debugger::new();
+    debugger.command("b 208", &mut cpu);
+    debugger.run(&mut cpu, 100).unwrap();
+    assert!(debugger.paused);
+    assert_eq!((cpu.pc(), cpu.v()[1]), (0x208, 1));
+
+    // Resuming doesn't stop on the same breakpoint again
+    debugger.command("c", &mut cpu);
+    debugger.run(&mut cpu, 100).unwrap();
+    assert!(!debugger.paused);
+    assert_eq!((cpu.pc(), cpu.v()[0], cpu.v()[1]), (0x204, 1, 2));
+
+    debugger.command("d 208", &mut cpu);
+    assert!(debugger.breakpoints.is_empty());
+}
+
+#[test]
+fn step() {
+    let mut cpu = load(&CALL);
+    let mut debugger = Debugger::new();
+    debugger.command("s", &mut cpu);
+    assert!(debugger.paused);
+    assert_eq!((cpu.pc(), cpu.sp()), (0x206, 1));
+    debugger.command("s 2", &mut cpu);
+    assert_eq!((cpu.pc(), cpu.v()[1]), (0x20A, 2));
+
+    // Paused, run does nothing
+    debugger.run(&mut cpu, 100).unwrap();
+    assert_eq!(cpu.pc(), 0x20A);
+}
+
+#[test]
+fn next_steps_over_calls() {
+    let mut cpu = load(&CALL);
+    let mut debugger = Debugger::new();
+    debugger.command("n", &mut cpu);
+    debugger.run(&mut cpu, 100).unwrap();
+    assert!(debugger.paused);
+    assert_eq!((cpu.pc(), cpu.sp(), cpu.v()[1]), (0x202, 0, 2));
+
+    // Anything else is a single step
+    debugger.command("n", &mut cpu);
+    assert!(debugger.paused);
+    assert_eq!((cpu.pc(), cpu.v()[0]), (0x204, 1));
+}
+
+#[test]
+fn next_at_the_end_of_memory() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::xochip();
+    cpu.load_rom(&[0x00, 0xEE]).unwrap();
+    cpu.write_memory(0xFFFE, &[0x22, 0x00]);
+    cpu.set_pc(0xFFFE);
+
+    let mut debugger = Debugger::new();
+    debugger.command("n", &mut cpu);
+    debugger.run(&mut cpu, 10).unwrap();
+    assert!(debugger.paused);
+    assert_eq!((cpu.pc(), cpu.sp()), (0, 0));
+}
+
+#[test]
+fn finish() {
+    let mut cpu = load(&CALL);
+    let mut debugger = Debugger::new();
+    debugger.command("f", &mut cpu);
+    assert!(!debugger.paused);
+
+    debugger.command("s", &mut cpu);
+    debugger.command("f", &mut cpu);
+    debugger.run(&mut cpu, 100).unwrap();
+    assert!(debugger.paused);
+    assert_eq!((cpu.pc(), cpu.sp(), cpu.v()[1]), (0x202, 0, 2));
+}
@@ -0,0 +1,232 @@
+// Errors from loading & running programs, & what each ErrorPolicy does with them
+use chip8::cpu::{Cpu, MemoryPolicy, MEMORY_SIZE};
+use chip8::debugger::Debugger;
+use chip8::error::{Chip8Error, ErrorPolicy};
+use chip8::quirks::Quirks;
+
+#[test]
+fn rom_too_large() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::xochip();
+    let rom = vec![0; MEMORY_SIZE];
+    match cpu.load_rom(&rom) {
+        Err(Chip8Error::RomTooLarge { size, max }) => {
+            assert_eq!(size, MEMORY_SIZE);
+            assert_eq!(max, MEMORY_SIZE - 0x200);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(cpu.load_rom(&rom[..5000]).is_ok());
+}
+
+#[test]
+fn rom_too_large_for_platform() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::chip8();
+    match cpu.load_rom(&[0; 5000]) {
+        Err(Chip8Error::RomTooLarge { size, max }) => {
+            assert_eq!(size, 5000);
+            assert_eq!(max, 0xE00);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(cpu.load_rom(&[0; 0xE00]).is_ok());
+}
+
+#[test]
+fn loading_clears_the_previous_rom() {
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&[1, 2, 3, 4]).unwrap();
+    cpu.load_rom(&[5, 6]).unwrap();
+    assert_eq!(&cpu.memory()[0x200..0x204], &[5, 6, 0, 0]);
+}
+
+#[test]
+fn missing_rom() {
+    let mut cpu = Cpu::new();
+    assert!(matches!(cpu.load_bin("roms/does-not-exist.ch8"), Err(Chip8Error::Io(_))));
+}
+
+#[test]
+fn unknown_opcode() {
+    let mut cpu = Cpu::new();
+    match cpu.execute(0x8008) {
+        Err(Chip8Error::UnknownOpcode { pc, opcode }) => {
+            assert_eq!(pc, 0x200);
+            assert_eq!(opcode, 0x8008);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    // Halted on the failing instruction
+    assert_eq!(cpu.pc(), 0x200);
+}
+
+#[test]
+fn stack_overflow() {
+    let mut cpu = Cpu::new();
+    for _ in 0..16 {
+        cpu.execute(0x2200).unwrap();
+    }
+    assert!(matches!(cpu.execute(0x2200), Err(Chip8Error::StackOverflow { pc: 0x200, .. })));
+    assert_eq!(cpu.sp(), 16);
+}
+
+#[test]
+fn stack_underflow() {
+    let mut cpu = Cpu::new();
+    assert!(matches!(cpu.execute(0x00EE), Err(Chip8Error::StackUnderflow { pc: 0x200 })));
+}
+
+#[test]
+fn memory_out_of_bounds() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::xochip();
+    cpu.memory_policy = MemoryPolicy::Trap;
+    cpu.set_i(0xFFFE);
+    match cpu.execute(0xF033) {
+        Err(Chip8Error::MemoryOutOfBounds { pc, addr }) => {
+            assert_eq!(pc, 0x200);
+            assert_eq!(addr, MEMORY_SIZE);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(cpu.execute(0xF055).is_ok());
+    // FX55 moved I on with XO-CHIP's load/store quirk
+    assert_eq!(cpu.i(), 0xFFFF);
+    // The store fits but leaves I past 16 bits
+    cpu.set_i(0xFFFE);
+    assert!(cpu.execute(0xF155).is_err());
+    assert_eq!(cpu.i(), 0xFFFE);
+    assert!(cpu.execute(0xF255).is_err());
+    assert!(cpu.execute(0xD011).is_ok());
+    assert!(cpu.execute(0xD013).is_err());
+}
+
+#[test]
+fn skip_policy_steps_over_errors() {
+    let mut cpu = Cpu::new();
+    cpu.on_error = ErrorPolicy::Skip;
+    cpu.execute(0xFFFF).unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+}
+
+#[test]
+fn trap_policy_pauses_the_debugger() {
+    let mut cpu = Cpu::new();
+    let mut debugger = Debugger::new();
+    cpu.write_memory(0x200, &[0x60, 0x01, 0xFF, 0xFF]);
+
+    assert!(debugger.run(&mut cpu, 10).is_err());
+    assert!(!debugger.paused);
+
+    cpu.set_pc(0x200);
+    cpu.on_error = ErrorPolicy::Trap;
+    debugger.run(&mut cpu, 10).unwrap();
+    assert!(debugger.paused);
+    assert_eq!(cpu.pc(), 0x202);
+}
+
+#[test]
+fn policy_survives_loading_a_state() {
+    let mut cpu = Cpu::new();
+    cpu.on_error = ErrorPolicy::Skip;
+    let state = cpu.save_state();
+    cpu.load_state(&state).unwrap();
+    assert_eq!(cpu.on_error, ErrorPolicy::Skip);
+}
+
+#[test]
+fn stack_depth_follows_the_platform() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::chip8();
+    for _ in 0..12 {
+        cpu.execute(0x2200).unwrap();
+    }
+    match cpu.execute(0x2200) {
+        Err(Chip8Error::StackOverflow { pc, calls }) => {
+            assert_eq!(pc, 0x200);
+            assert_eq!(calls, vec![0x200; 12]);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn unlimited_stack() {
+    let mut cpu = Cpu::new();
+    cpu.quirks.stack_depth = Quirks::parse_stack_depth("unlimited").unwrap();
+    for _ in 0..1000 {
+        cpu.execute(0x2200).unwrap();
+    }
+    assert_eq!(cpu.sp(), 1000);
+
+    // The stack survives a save state round trip
+    let state = cpu.save_state();
+    let mut copy = Cpu::new();
+    copy.load_state(&state).unwrap();
+    assert_eq!(copy.stack(), cpu.stack());
+    assert_eq!(copy.quirks.stack_depth, None);
+}
+
+#[test]
+fn overflow_shows_the_call_chain() {
+    let e = Chip8Error::StackOverflow { pc: 0x300, calls: vec![0x200, 0x250] };
+    assert_eq!(e.to_string(), "Stack overflow at #0300, 2 calls deep. Call chain: #0200 > #0250 > #0300");
+}
+
+#[test]
+fn memory_traps_at_the_platform_size() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::schip11();
+    cpu.memory_policy = MemoryPolicy::Trap;
+    cpu.set_i(0xFFE);
+    cpu.execute(0xF155).unwrap();
+    for &opcode in [0xF033, 0xF255, 0xF265, 0xD013, 0x5023, 0x5022, 0xF002].iter() {
+        cpu.set_pc(0x200);
+        match cpu.execute(opcode) {
+            Err(Chip8Error::MemoryOutOfBounds { addr, .. }) => assert_eq!(addr, 0x1000),
+            other => panic!("{:04X}: unexpected {:?}", opcode, other),
+        }
+    }
+}
+
+#[test]
+fn index_past_16_bits_traps() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::xochip();
+    cpu.memory_policy = MemoryPolicy::Trap;
+    cpu.set_i(0xFFFF);
+    cpu.set_v(1, 2);
+    match cpu.execute(0xF11E) {
+        Err(Chip8Error::MemoryOutOfBounds { addr, .. }) => assert_eq!(addr, 0x10001),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(cpu.i(), 0xFFFF);
+
+    cpu.set_i(0xFFFD);
+    cpu.execute(0xF11E).unwrap();
+    assert_eq!(cpu.i(), 0xFFFF);
+}
+
+#[test]
+fn pc_wraps_at_the_end_of_memory() {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::xochip();
+    cpu.set_pc(0xFFFE);
+    cpu.execute(0x6001).unwrap();
+    assert_eq!(cpu.pc(), 0);
+
+    // Skipping over the last word & F000 NNNN straddling the end
+    cpu.set_pc(0xFFFE);
+    cpu.execute(0x3001).unwrap();
+    assert_eq!(cpu.pc(), 2);
+    cpu.write_memory(0, &[0xF0, 0x00]);
+    cpu.set_pc(0xFFFE);
+    cpu.execute(0x3001).unwrap();
+    assert_eq!(cpu.pc(), 4);
+
+    let mut cpu = Cpu::new();
+    cpu.set_pc(0xFFE);
+    cpu.execute(0x6001).unwrap();
+    assert_eq!(cpu.pc(), 0);
+}
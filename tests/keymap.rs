@@ -0,0 +1,70 @@
+// Keypad layouts, rebinding from the config file & clashing bindings
+use chip8::config::Config;
+use chip8::keymap::{Hotkey, Keymap};
+use chip8::options::Options;
+
+#[test]
+fn vip_grid() {
+    let keymap = Keymap::vip();
+    // 1 2 3 C / 4 5 6 D / 7 8 9 E / A 0 B F
+    let rows: Vec<&str> = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF]
+        .iter()
+        .map(|&k| keymap.keys[k].as_str())
+        .collect();
+    assert_eq!(rows.concat(), "1234QWERASDFZXCV");
+    assert_eq!(keymap.hotkeys[&Hotkey::Exit], "Escape");
+    assert_eq!(keymap.clash(), None);
+}
+
+#[test]
+fn hex_row() {
+    let keymap = Keymap::hex();
+    assert_eq!(keymap.keys.concat(), "0123456789ABCDEF");
+    assert_eq!(keymap.clash(), None);
+    assert_eq!(Options::default().keymap(), Keymap::vip());
+}
+
+#[test]
+fn rebinding() {
+    let config = Config::parse(
+        r#"
+keymap = "hex"
+
+[hotkeys]
+exit = "F10"
+
+[rom."INVADERS".keys]
+4 = "Left"
+6 = "Right"
+5 = "Space"
+"#,
+    )
+    .unwrap();
+
+    let keymap = config.options("roms/INVADERS", "", None).unwrap().keymap();
+    assert_eq!(keymap.keys[4], "Left");
+    assert_eq!(keymap.keys[5], "Space");
+    assert_eq!(keymap.keys[7], "7");
+    assert_eq!(keymap.hotkeys[&Hotkey::Exit], "F10");
+
+    let args = vec!["--keymap".to_string(), "vip".to_string()];
+    let keymap = Options::parse(&args).unwrap().or(config.options("roms/INVADERS", "", None).unwrap()).keymap();
+    assert_eq!(keymap.keys[1], "1");
+    assert_eq!(keymap.keys[6], "Right");
+}
+
+#[test]
+fn clash() {
+    let mut keymap = Keymap::vip();
+    keymap.hotkeys.insert(Hotkey::Mute, "q".to_string());
+    assert_eq!(keymap.clash().unwrap(), "q is bound to both key 4 & mute");
+}
+
+#[test]
+fn errors() {
+    let config = Config::parse("[keys]\n10 = \"Up\"").unwrap();
+    assert_eq!(config.options("a.ch8", "", None).unwrap_err(), "Invalid keypad key: 10");
+    let config = Config::parse("[hotkeys]\nquit = \"Q\"").unwrap();
+    assert_eq!(config.options("a.ch8", "", None).unwrap_err(), "Unknown hotkey: quit");
+    assert!(Options::parse(&["--keymap".to_string(), "azerty".to_string()]).is_err());
+}
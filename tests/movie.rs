@@ -0,0 +1,34 @@
+// Movie files round trip & damaged ones are reported as errors
+use chip8::cpu::Cpu;
+use chip8::movie::Movie;
+use chip8::quirks::Quirks;
+
+fn recording() -> Movie {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::xochip();
+    cpu.seed_rng(42);
+    let mut movie = Movie::new(&cpu);
+    for key in 0..3 {
+        cpu.set_key(key, true);
+        movie.record(&cpu);
+    }
+    movie
+}
+
+#[test]
+fn round_trip() {
+    let movie = recording();
+    let loaded = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(loaded.seed, 42);
+    assert_eq!(loaded.quirks, Quirks::xochip());
+    assert_eq!(loaded.frames, movie.frames);
+}
+
+#[test]
+fn damaged_files() {
+    let bytes = recording().to_bytes();
+    assert!(Movie::from_bytes(&bytes[..10]).is_err());
+    assert!(Movie::from_bytes(b"C8ST\x01\x00").is_err());
+    assert!(Movie::from_bytes(&[]).is_err());
+    assert!(Movie::load("roms/does-not-exist.c8m").is_err());
+}
@@ -0,0 +1,786 @@
+// One or more tests per opcode in Cpu::run, quirk dependent opcodes are checked under each setting.
+use chip8::cpu::{Cpu, KeyWait};
+use chip8::debugger::Debugger;
+use chip8::quirks::Quirks;
+use chip8::video::DisplayMode;
+
+fn cpu() -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.seed_rng(0);
+    cpu
+}
+
+fn with_quirks(quirks: Quirks) -> Cpu {
+    let mut cpu = cpu();
+    cpu.quirks = quirks;
+    cpu
+}
+
+// Lit pixels as (x, y) pairs, in row order
+fn lit(cpu: &Cpu) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    for (y, row) in cpu.pixels.iter().enumerate() {
+        for (x, &pixel) in row.iter().enumerate() {
+            if pixel != 0 {
+                out.push((x, y));
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn cls_clears_the_screen() {
+    let mut cpu = cpu();
+    cpu.pixels[3][5] = 1;
+    cpu.execute(0x00E0).unwrap();
+    assert!(lit(&cpu).is_empty());
+    assert!(cpu.draw_flag);
+    assert_eq!(cpu.pc(), 0x202);
+}
+
+#[test]
+fn cls_only_clears_selected_planes() {
+    let mut cpu = cpu();
+    cpu.pixels[0][0] = 3;
+    cpu.plane = 1;
+    cpu.execute(0x00E0).unwrap();
+    assert_eq!(cpu.pixels[0][0], 2);
+}
+
+#[test]
+fn call_and_return() {
+    let mut cpu = cpu();
+    cpu.execute(0x2345).unwrap();
+    assert_eq!(cpu.pc(), 0x345);
+    assert_eq!(cpu.stack(), &[0x200]);
+
+    cpu.execute(0x00EE).unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+    assert_eq!(cpu.sp(), 0);
+}
+
+#[test]
+fn jump() {
+    let mut cpu = cpu();
+    cpu.execute(0x1ABC).unwrap();
+    assert_eq!(cpu.pc(), 0xABC);
+    assert_eq!(cpu.sp(), 0);
+}
+
+#[test]
+fn scroll_down() {
+    let mut cpu = cpu();
+    cpu.display_mode = DisplayMode::Extended;
+    cpu.pixels[0][7] = 1;
+    cpu.execute(0x00C3).unwrap();
+    assert_eq!(lit(&cpu), vec![(7, 3)]);
+}
+
+#[test]
+fn scroll_down_lores_moves_half_as_many_lines() {
+    let mut cpu = cpu();
+    cpu.pixels[0][7] = 1;
+    cpu.execute(0x00C4).unwrap();
+    assert_eq!(lit(&cpu), vec![(7, 2)]);
+}
+
+#[test]
+fn scroll_up() {
+    let mut cpu = cpu();
+    cpu.display_mode = DisplayMode::Extended;
+    cpu.pixels[10][7] = 1;
+    cpu.pixels[1][0] = 1;
+    cpu.execute(0x00D2).unwrap();
+    assert_eq!(lit(&cpu), vec![(7, 8)]);
+}
+
+#[test]
+fn xochip_scrolls_whole_lores_pixels() {
+    let mut cpu = with_quirks(Quirks::xochip());
+    cpu.pixels[5][10] = 1;
+    cpu.execute(0x00D1).unwrap();
+    assert_eq!(lit(&cpu), vec![(10, 4)]);
+    cpu.execute(0x00C3).unwrap();
+    assert_eq!(lit(&cpu), vec![(10, 7)]);
+    cpu.execute(0x00FB).unwrap();
+    assert_eq!(lit(&cpu), vec![(14, 7)]);
+    cpu.execute(0x00FC).unwrap();
+    assert_eq!(lit(&cpu), vec![(10, 7)]);
+}
+
+#[test]
+fn scroll_right() {
+    let mut cpu = cpu();
+    cpu.display_mode = DisplayMode::Extended;
+    cpu.pixels[5][10] = 1;
+    cpu.pixels[6][127] = 1;
+    cpu.execute(0x00FB).unwrap();
+    assert_eq!(lit(&cpu), vec![(14, 5)]);
+
+    let mut cpu = self::cpu();
+    cpu.pixels[5][10] = 1;
+    cpu.execute(0x00FB).unwrap();
+    assert_eq!(lit(&cpu), vec![(12, 5)]);
+}
+
+#[test]
+fn scroll_left() {
+    let mut cpu = cpu();
+    cpu.display_mode = DisplayMode::Extended;
+    cpu.pixels[5][10] = 1;
+    cpu.pixels[6][1] = 1;
+    cpu.execute(0x00FC).unwrap();
+    assert_eq!(lit(&cpu), vec![(6, 5)]);
+
+    let mut cpu = self::cpu();
+    cpu.pixels[5][10] = 1;
+    cpu.execute(0x00FC).unwrap();
+    assert_eq!(lit(&cpu), vec![(8, 5)]);
+}
+
+#[test]
+fn scroll_only_moves_selected_planes() {
+    let mut cpu = cpu();
+    cpu.display_mode = DisplayMode::Extended;
+    cpu.pixels[0][0] = 3;
+    cpu.plane = 2;
+    cpu.execute(0x00C1).unwrap();
+    assert_eq!(cpu.pixels[0][0], 1);
+    assert_eq!(cpu.pixels[1][0], 2);
+}
+
+#[test]
+fn display_modes() {
+    let mut cpu = cpu();
+    cpu.execute(0x00FF).unwrap();
+    assert_eq!(cpu.display_mode, DisplayMode::Extended);
+    cpu.execute(0x00FE).unwrap();
+    assert_eq!(cpu.display_mode, DisplayMode::Normal);
+    assert_eq!(cpu.pc(), 0x204);
+}
+
+#[test]
+fn exit_resets() {
+    let mut cpu = cpu();
+    cpu.set_pc(0x300);
+    cpu.set_v(3, 9);
+    cpu.execute(0x00FD).unwrap();
+    assert_eq!(cpu.pc(), 0x200);
+    assert_eq!(cpu.v()[3], 0);
+}
+
+#[test]
+fn skip_if_equal_byte() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0x42);
+    cpu.execute(0x3142).unwrap();
+    assert_eq!(cpu.pc(), 0x204);
+    cpu.execute(0x3143).unwrap();
+    assert_eq!(cpu.pc(), 0x206);
+}
+
+#[test]
+fn skip_if_not_equal_byte() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0x42);
+    cpu.execute(0x4142).unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+    cpu.execute(0x4143).unwrap();
+    assert_eq!(cpu.pc(), 0x206);
+}
+
+#[test]
+fn skip_steps_over_long_load() {
+    let mut cpu = cpu();
+    cpu.write_memory(0x202, &[0xF0, 0x00, 0x12, 0x34]);
+    cpu.execute(0x3000).unwrap();
+    assert_eq!(cpu.pc(), 0x206);
+}
+
+#[test]
+fn skip_if_registers_equal() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 7);
+    cpu.set_v(2, 7);
+    cpu.execute(0x5120).unwrap();
+    assert_eq!(cpu.pc(), 0x204);
+    cpu.set_v(2, 8);
+    cpu.execute(0x5120).unwrap();
+    assert_eq!(cpu.pc(), 0x206);
+}
+
+#[test]
+fn skip_if_registers_not_equal() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 7);
+    cpu.set_v(2, 7);
+    cpu.execute(0x9120).unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+    cpu.set_v(2, 8);
+    cpu.execute(0x9120).unwrap();
+    assert_eq!(cpu.pc(), 0x206);
+    // Only 9XY0 is defined
+    assert!(cpu.execute(0x9121).is_err());
+}
+
+#[test]
+fn save_register_range() {
+    let mut cpu = cpu();
+    cpu.set_i(0x300);
+    for (reg, value) in [(2, 1), (3, 2), (4, 3)].iter() {
+        cpu.set_v(*reg, *value);
+    }
+    cpu.execute(0x5242).unwrap();
+    assert_eq!(&cpu.memory()[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(cpu.i(), 0x300);
+
+    // Descending ranges store Vx first
+    cpu.execute(0x5422).unwrap();
+    assert_eq!(&cpu.memory()[0x300..0x303], &[3, 2, 1]);
+}
+
+#[test]
+fn load_register_range() {
+    let mut cpu = cpu();
+    cpu.set_i(0x300);
+    cpu.write_memory(0x300, &[1, 2, 3]);
+    cpu.execute(0x5243).unwrap();
+    assert_eq!(&cpu.v()[2..5], &[1, 2, 3]);
+    assert_eq!(cpu.i(), 0x300);
+
+    cpu.execute(0x5423).unwrap();
+    assert_eq!(&cpu.v()[2..5], &[3, 2, 1]);
+}
+
+#[test]
+fn load_byte() {
+    let mut cpu = cpu();
+    cpu.execute(0x6A5F).unwrap();
+    assert_eq!(cpu.v()[0xA], 0x5F);
+}
+
+#[test]
+fn add_byte_wraps_without_carry() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0xFF);
+    cpu.execute(0x7102).unwrap();
+    assert_eq!(cpu.v()[1], 0x01);
+    assert_eq!(cpu.v()[0xF], 0);
+}
+
+#[test]
+fn register_moves_and_logic() {
+    let mut cpu = cpu();
+    cpu.set_v(2, 0b1100);
+    cpu.execute(0x8120).unwrap();
+    assert_eq!(cpu.v()[1], 0b1100);
+
+    cpu.set_v(1, 0b1010);
+    cpu.execute(0x8121).unwrap();
+    assert_eq!(cpu.v()[1], 0b1110);
+
+    cpu.set_v(1, 0b1010);
+    cpu.execute(0x8122).unwrap();
+    assert_eq!(cpu.v()[1], 0b1000);
+
+    cpu.set_v(1, 0b1010);
+    cpu.execute(0x8123).unwrap();
+    assert_eq!(cpu.v()[1], 0b0110);
+}
+
+#[test]
+fn logic_vf_reset_quirk() {
+    for &opcode in [0x8121, 0x8122, 0x8123].iter() {
+        let mut cpu = cpu();
+        cpu.set_v(0xF, 5);
+        cpu.execute(opcode).unwrap();
+        assert_eq!(cpu.v()[0xF], 5);
+
+        let mut cpu = with_quirks(Quirks::chip8());
+        cpu.set_v(0xF, 5);
+        cpu.execute(opcode).unwrap();
+        assert_eq!(cpu.v()[0xF], 0);
+    }
+}
+
+#[test]
+fn add_registers_sets_carry() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0xF0);
+    cpu.set_v(2, 0x20);
+    cpu.execute(0x8124).unwrap();
+    assert_eq!(cpu.v()[1], 0x10);
+    assert_eq!(cpu.v()[0xF], 1);
+
+    cpu.set_v(1, 0x10);
+    cpu.execute(0x8124).unwrap();
+    assert_eq!(cpu.v()[1], 0x30);
+    assert_eq!(cpu.v()[0xF], 0);
+
+    // 0xFF is the largest sum without a carry
+    cpu.set_v(1, 0xDF);
+    cpu.execute(0x8124).unwrap();
+    assert_eq!(cpu.v()[1], 0xFF);
+    assert_eq!(cpu.v()[0xF], 0);
+}
+
+#[test]
+fn add_registers_carry_overwrites_vf() {
+    let mut cpu = cpu();
+    cpu.set_v(0xF, 0xFF);
+    cpu.set_v(1, 0x02);
+    cpu.execute(0x8F14).unwrap();
+    assert_eq!(cpu.v()[0xF], 1);
+}
+
+#[test]
+fn subtract_registers_sets_not_borrow() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0x30);
+    cpu.set_v(2, 0x10);
+    cpu.execute(0x8125).unwrap();
+    assert_eq!(cpu.v()[1], 0x20);
+    assert_eq!(cpu.v()[0xF], 1);
+
+    cpu.set_v(1, 0x10);
+    cpu.set_v(2, 0x30);
+    cpu.execute(0x8125).unwrap();
+    assert_eq!(cpu.v()[1], 0xE0);
+    assert_eq!(cpu.v()[0xF], 0);
+
+    // Equal values don't borrow
+    cpu.set_v(1, 0x30);
+    cpu.execute(0x8125).unwrap();
+    assert_eq!(cpu.v()[1], 0);
+    assert_eq!(cpu.v()[0xF], 1);
+}
+
+#[test]
+fn subtract_registers_flag_overwrites_vf() {
+    let mut cpu = cpu();
+    cpu.set_v(0xF, 0x10);
+    cpu.set_v(1, 0x20);
+    cpu.execute(0x8F15).unwrap();
+    assert_eq!(cpu.v()[0xF], 0);
+}
+
+#[test]
+fn reverse_subtract_sets_not_borrow() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0x10);
+    cpu.set_v(2, 0x30);
+    cpu.execute(0x8127).unwrap();
+    assert_eq!(cpu.v()[1], 0x20);
+    assert_eq!(cpu.v()[0xF], 1);
+
+    cpu.set_v(1, 0x30);
+    cpu.set_v(2, 0x10);
+    cpu.execute(0x8127).unwrap();
+    assert_eq!(cpu.v()[1], 0xE0);
+    assert_eq!(cpu.v()[0xF], 0);
+}
+
+#[test]
+fn shift_right() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0b101);
+    cpu.set_v(2, 0xFE);
+    cpu.execute(0x8126).unwrap();
+    assert_eq!(cpu.v()[1], 0b10);
+    assert_eq!(cpu.v()[0xF], 1);
+
+    // COSMAC VIP shifts Vy into Vx
+    let mut cpu = with_quirks(Quirks::chip8());
+    cpu.set_v(1, 0b101);
+    cpu.set_v(2, 0xFE);
+    cpu.execute(0x8126).unwrap();
+    assert_eq!(cpu.v()[1], 0x7F);
+    assert_eq!(cpu.v()[0xF], 0);
+}
+
+#[test]
+fn shift_left() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0x81);
+    cpu.set_v(2, 0x01);
+    cpu.execute(0x812E).unwrap();
+    assert_eq!(cpu.v()[1], 0x02);
+    assert_eq!(cpu.v()[0xF], 1);
+
+    let mut cpu = with_quirks(Quirks::chip8());
+    cpu.set_v(1, 0x81);
+    cpu.set_v(2, 0x01);
+    cpu.execute(0x812E).unwrap();
+    assert_eq!(cpu.v()[1], 0x02);
+    assert_eq!(cpu.v()[0xF], 0);
+}
+
+#[test]
+fn load_index() {
+    let mut cpu = cpu();
+    cpu.execute(0xA123).unwrap();
+    assert_eq!(cpu.i(), 0x123);
+}
+
+#[test]
+fn jump_with_offset() {
+    let mut cpu = cpu();
+    cpu.set_v(0, 4);
+    cpu.set_v(3, 2);
+    cpu.execute(0xB312).unwrap();
+    assert_eq!(cpu.pc(), 0x316);
+
+    // CHIP-48 & SCHIP add Vx instead of V0
+    let mut cpu = with_quirks(Quirks::schip11());
+    cpu.set_v(0, 4);
+    cpu.set_v(3, 2);
+    cpu.execute(0xB312).unwrap();
+    assert_eq!(cpu.pc(), 0x314);
+}
+
+#[test]
+fn random_is_masked_and_seeded() {
+    let mut cpu = cpu();
+    for _ in 0..50 {
+        cpu.set_pc(0x200);
+        cpu.execute(0xC10F).unwrap();
+        assert_eq!(cpu.v()[1] & 0xF0, 0);
+    }
+    cpu.set_pc(0x200);
+    cpu.execute(0xC100).unwrap();
+    assert_eq!(cpu.v()[1], 0);
+
+    let mut a = self::cpu();
+    let mut b = self::cpu();
+    let rolls = |cpu: &mut Cpu| {
+        (0..8)
+            .map(|_| {
+                cpu.set_pc(0x200);
+                cpu.execute(0xC1FF).unwrap();
+                cpu.v()[1]
+            })
+            .collect::<Vec<u8>>()
+    };
+    assert_eq!(rolls(&mut a), rolls(&mut b));
+}
+
+#[test]
+fn draw_sprite_and_collide() {
+    let mut cpu = cpu();
+    cpu.write_memory(0x300, &[0b1010_0000, 0b0100_0000]);
+    cpu.set_i(0x300);
+    cpu.set_v(1, 3);
+    cpu.set_v(2, 4);
+    cpu.execute(0xD122).unwrap();
+    assert_eq!(lit(&cpu), vec![(3, 4), (5, 4), (4, 5)]);
+    assert_eq!(cpu.v()[0xF], 0);
+    assert!(cpu.draw_flag);
+
+    // Drawing the same sprite again erases it & reports the collision
+    cpu.execute(0xD122).unwrap();
+    assert!(lit(&cpu).is_empty());
+    assert_eq!(cpu.v()[0xF], 1);
+}
+
+#[test]
+fn draw_wraps_or_clips() {
+    let mut cpu = cpu();
+    cpu.write_memory(0x300, &[0xC0]);
+    cpu.set_i(0x300);
+    cpu.set_v(1, 63);
+    cpu.execute(0xD121).unwrap();
+    assert_eq!(lit(&cpu), vec![(0, 0), (63, 0)]);
+
+    let mut cpu = with_quirks(Quirks { clip: true, ..Quirks::default() });
+    cpu.write_memory(0x300, &[0xC0]);
+    cpu.set_i(0x300);
+    cpu.set_v(1, 63);
+    cpu.execute(0xD121).unwrap();
+    assert_eq!(lit(&cpu), vec![(63, 0)]);
+
+    // The origin always wraps
+    cpu.set_v(1, 64 + 2);
+    cpu.set_v(2, 32 + 1);
+    cpu.execute(0xD121).unwrap();
+    assert_eq!(lit(&cpu), vec![(63, 0), (2, 1), (3, 1)]);
+}
+
+#[test]
+fn draw_extended_sprite() {
+    let mut cpu = cpu();
+    let mut sprite = [0u8; 32];
+    sprite[0] = 0x80;
+    sprite[31] = 0x01;
+    cpu.write_memory(0x300, &sprite);
+    cpu.set_i(0x300);
+    cpu.execute(0x00FF).unwrap();
+    cpu.execute(0xD120).unwrap();
+    assert_eq!(lit(&cpu), vec![(0, 0), (15, 15)]);
+}
+
+#[test]
+fn draw_both_planes() {
+    let mut cpu = cpu();
+    cpu.write_memory(0x300, &[0x80, 0xC0]);
+    cpu.set_i(0x300);
+    cpu.execute(0xF301).unwrap();
+    cpu.execute(0xD121).unwrap();
+    assert_eq!(cpu.pixels[0][0], 3);
+    assert_eq!(cpu.pixels[0][1], 2);
+    assert_eq!(cpu.last_access().map(|a| a.len), Some(2));
+}
+
+#[test]
+fn draw_waits_for_display() {
+    let mut cpu = with_quirks(Quirks::chip8());
+    cpu.execute(0xD121).unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+
+    // The second sprite waits for the next frame
+    cpu.execute(0xD121).unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+    cpu.update_timers(16.0);
+    cpu.run().unwrap();
+    assert_eq!(cpu.pc(), 0x204);
+}
+
+#[test]
+fn skip_on_key() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0xA);
+    cpu.execute(0xE19E).unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+    cpu.execute(0xE1A1).unwrap();
+    assert_eq!(cpu.pc(), 0x206);
+
+    cpu.set_key(0xA, true);
+    cpu.execute(0xE19E).unwrap();
+    assert_eq!(cpu.pc(), 0x20A);
+    cpu.execute(0xE1A1).unwrap();
+    assert_eq!(cpu.pc(), 0x20C);
+}
+
+#[test]
+fn wait_for_key_press_and_release() {
+    let mut cpu = cpu();
+    assert_eq!(Debugger::key_wait(&cpu), "none");
+    cpu.execute(0xF30A).unwrap();
+    assert_eq!(cpu.pc(), 0x200);
+    assert_eq!(cpu.key_wait, KeyWait::Waiting);
+    assert_eq!(Debugger::key_wait(&cpu), "V3, waiting for a key press");
+
+    cpu.set_key(7, true);
+    cpu.run().unwrap();
+    cpu.run().unwrap();
+    assert_eq!(cpu.pc(), 0x200);
+    assert_eq!(cpu.key_wait, KeyWait::Held(7));
+    assert_eq!(Debugger::key_wait(&cpu), "V3, waiting for key 7 to be released");
+
+    cpu.set_key(7, false);
+    cpu.run().unwrap();
+    assert_eq!(cpu.pc(), 0x202);
+    assert_eq!(cpu.v()[3], 7);
+    assert_eq!(cpu.key_wait, KeyWait::Idle);
+}
+
+#[test]
+fn long_index_load() {
+    let mut cpu = with_quirks(Quirks::xochip());
+    cpu.write_memory(0x200, &[0xF0, 0x00, 0x12, 0x34]);
+    cpu.run().unwrap();
+    assert_eq!(cpu.i(), 0x1234);
+    assert_eq!(cpu.pc(), 0x204);
+}
+
+#[test]
+fn audio_pattern_and_pitch() {
+    let mut cpu = cpu();
+    let pattern: Vec<u8> = (0..16).collect();
+    cpu.write_memory(0x300, &pattern);
+    cpu.set_i(0x300);
+    cpu.execute(0xF002).unwrap();
+    assert_eq!(&cpu.audio_pattern[..], &pattern[..]);
+
+    cpu.set_v(4, 112);
+    cpu.execute(0xF43A).unwrap();
+    assert_eq!(cpu.pitch, 112);
+}
+
+#[test]
+fn timers() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 10);
+    cpu.execute(0xF115).unwrap();
+    cpu.execute(0xF118).unwrap();
+    cpu.update_timers(16.0);
+    cpu.execute(0xF207).unwrap();
+    assert_eq!(cpu.v()[2], 9);
+    assert_eq!(cpu.sound_timer(), 9);
+    assert!(cpu.beeping());
+}
+
+#[test]
+fn add_to_index() {
+    let mut cpu = cpu();
+    cpu.set_i(0x100);
+    cpu.set_v(1, 0x20);
+    cpu.execute(0xF11E).unwrap();
+    assert_eq!(cpu.i(), 0x120);
+    assert_eq!(cpu.v()[0xF], 0);
+
+    cpu.set_i(0xFFF);
+    cpu.set_v(1, 1);
+    cpu.execute(0xF11E).unwrap();
+    assert_eq!(cpu.i(), 0x000);
+    assert_eq!(cpu.v()[0xF], 1);
+
+    // XO-CHIP's 64 KiB reach past 4 KiB, VF is only set at the end of its memory
+    let mut cpu = with_quirks(Quirks::xochip());
+    cpu.set_i(0xFFF);
+    cpu.set_v(1, 1);
+    cpu.execute(0xF11E).unwrap();
+    assert_eq!(cpu.i(), 0x1000);
+    assert_eq!(cpu.v()[0xF], 0);
+
+    cpu.set_i(0xFFFF);
+    cpu.execute(0xF11E).unwrap();
+    assert_eq!(cpu.i(), 0);
+    assert_eq!(cpu.v()[0xF], 1);
+}
+
+#[test]
+fn font_characters() {
+    let mut cpu = cpu();
+    cpu.set_v(1, 0xA);
+    cpu.execute(0xF129).unwrap();
+    assert_eq!(cpu.i(), 50);
+    assert_eq!(&cpu.memory()[50..55], &[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+
+    cpu.set_v(1, 9);
+    cpu.execute(0xF130).unwrap();
+    assert_eq!(cpu.i(), 170);
+    assert_eq!(cpu.memory()[170], 0xFF);
+}
+
+#[test]
+fn binary_coded_decimal() {
+    let mut cpu = cpu();
+    cpu.set_i(0x300);
+    for &(value, digits) in [(254, [2, 5, 4]), (100, [1, 0, 0]), (9, [0, 0, 9]), (0, [0, 0, 0])].iter() {
+        cpu.set_v(1, value);
+        cpu.execute(0xF133).unwrap();
+        assert_eq!(&cpu.memory()[0x300..0x303], &digits);
+    }
+    assert_eq!(cpu.i(), 0x300);
+}
+
+#[test]
+fn store_and_load_registers() {
+    let mut cpu = cpu();
+    cpu.set_i(0x300);
+    for reg in 0..4 {
+        cpu.set_v(reg, reg as u8 + 1);
+    }
+    cpu.execute(0xF255).unwrap();
+    assert_eq!(&cpu.memory()[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(cpu.i(), 0x300);
+
+    cpu.write_memory(0x300, &[9, 8, 7, 6]);
+    cpu.execute(0xF265).unwrap();
+    assert_eq!(&cpu.v()[..4], &[9, 8, 7, 4]);
+    assert_eq!(cpu.i(), 0x300);
+}
+
+#[test]
+fn store_and_load_increment_index_quirk() {
+    let mut cpu = with_quirks(Quirks::chip8());
+    cpu.set_i(0x300);
+    cpu.execute(0xF255).unwrap();
+    assert_eq!(cpu.i(), 0x303);
+    cpu.execute(0xF065).unwrap();
+    assert_eq!(cpu.i(), 0x304);
+}
+
+#[test]
+fn user_flags() {
+    let mut cpu = cpu();
+    for reg in 0..4 {
+        cpu.set_v(reg, reg as u8 + 1);
+    }
+    cpu.execute(0xF375).unwrap();
+    for reg in 0..4 {
+        cpu.set_v(reg, 0);
+    }
+    cpu.execute(0xF285).unwrap();
+    assert_eq!(&cpu.v()[..4], &[1, 2, 3, 0]);
+}
+
+#[test]
+fn index_accesses_wrap_at_4k() {
+    let mut cpu = with_quirks(Quirks::chip8());
+    cpu.set_i(0xFFF);
+    cpu.set_v(0, 254);
+    cpu.execute(0xF033).unwrap();
+    assert_eq!(cpu.memory()[0xFFF], 2);
+    assert_eq!(&cpu.memory()[0..2], &[5, 4]);
+
+    // FX55 leaves I wrapped with the load/store quirk
+    for reg in 0..4 {
+        cpu.set_v(reg, reg as u8 + 1);
+    }
+    cpu.set_i(0xFFE);
+    cpu.execute(0xF355).unwrap();
+    assert_eq!(cpu.i(), 0x002);
+    assert_eq!(&cpu.memory()[0xFFE..0x1001], &[1, 2, 0]);
+    assert_eq!(&cpu.memory()[0..2], &[3, 4]);
+
+    cpu.set_i(0xFFF);
+    cpu.execute(0xF165).unwrap();
+    assert_eq!(&cpu.v()[..2], &[2, 3]);
+}
+
+#[test]
+fn default_preset_wraps_at_4k() {
+    let mut cpu = cpu();
+    cpu.set_i(0xFFF);
+    cpu.set_v(0, 254);
+    cpu.execute(0xF033).unwrap();
+    assert_eq!(cpu.memory()[0xFFF], 2);
+    assert_eq!(&cpu.memory()[0..2], &[5, 4]);
+    assert_eq!(cpu.memory()[0x1000], 0);
+
+    for reg in 0..3 {
+        cpu.set_v(reg, reg as u8 + 1);
+    }
+    cpu.set_i(0xFFE);
+    cpu.execute(0xF255).unwrap();
+    assert_eq!(&cpu.memory()[0xFFE..0x1001], &[1, 2, 0]);
+    assert_eq!(cpu.memory()[0], 3);
+
+    cpu.write_memory(0xFFF, &[0x80]);
+    cpu.write_memory(0, &[0x40]);
+    cpu.set_i(0xFFF);
+    cpu.execute(0xD452).unwrap();
+    assert_eq!(lit(&cpu), vec![(0, 0), (1, 1)]);
+}
+
+#[test]
+fn add_to_index_wraps_at_4k() {
+    let mut cpu = with_quirks(Quirks::schip11());
+    cpu.set_i(0xFFF);
+    cpu.set_v(1, 2);
+    cpu.execute(0xF11E).unwrap();
+    assert_eq!(cpu.i(), 0x001);
+    assert_eq!(cpu.v()[0xF], 1);
+}
+
+#[test]
+fn sprites_wrap_at_4k() {
+    let mut cpu = with_quirks(Quirks::schip11());
+    cpu.write_memory(0xFFF, &[0x80]);
+    cpu.write_memory(0, &[0x40]);
+    cpu.set_i(0xFFF);
+    cpu.execute(0xD012).unwrap();
+    assert_eq!(lit(&cpu), vec![(0, 0), (1, 1)]);
+}
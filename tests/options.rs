@@ -0,0 +1,68 @@
+// Command line parsing & how the flags land on the machine
+use chip8::cpu::{Cpu, MemoryPolicy};
+use chip8::error::ErrorPolicy;
+use chip8::options::Options;
+use chip8::quirks::Quirks;
+
+fn parse(args: &str) -> Result<Options, String> {
+    let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+    Options::parse(&args)
+}
+
+#[test]
+fn rom_only() {
+    let options = parse("roms/BRIX.ch8").unwrap();
+    assert_eq!(options.rom.as_deref(), Some("roms/BRIX.ch8"));
+    assert_eq!(options, Options { rom: options.rom.clone(), ..Options::default() });
+}
+
+#[test]
+fn flags() {
+    let options = parse(
+        "--preset chip8 --cycles=20 --seed 1234 --stack unlimited --memory trap --on-error skip \
+         --scale 5 --palette 000000,FFFFFF --fullscreen --mute --frames 60 --headless \
+         --paused --break 2A4 --break BRIX.SRC:12 --trace game.ch8",
+    )
+    .unwrap();
+    assert_eq!(options.rom.as_deref(), Some("game.ch8"));
+    assert_eq!(options.preset, Some(Quirks::chip8()));
+    assert_eq!(options.cycles, Some(20));
+    assert_eq!(options.seed, Some(1234));
+    assert_eq!(options.stack_depth, Some(None));
+    assert_eq!(options.memory, Some(MemoryPolicy::Trap));
+    assert_eq!(options.on_error, Some(ErrorPolicy::Skip));
+    assert_eq!(options.scale, Some(5));
+    assert_eq!(options.palette.unwrap()[1], (0xFF, 0xFF, 0xFF));
+    assert_eq!((options.fullscreen, options.mute), (Some(true), Some(true)));
+    assert!(options.headless && options.paused && options.trace);
+    assert_eq!(options.frames, Some(60));
+    assert_eq!(options.breakpoints, ["2A4", "BRIX.SRC:12"]);
+}
+
+#[test]
+fn help() {
+    assert!(parse("-h").unwrap().help);
+    assert!(parse("--help").unwrap().help);
+    let usage = Options::usage();
+    assert!(usage.contains("chip48") && usage.contains("trap"));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(parse("--preset nes").unwrap_err(), "Unknown quirks preset: nes");
+    assert_eq!(parse("--cycles fast").unwrap_err(), "Invalid number for --cycles: fast");
+    assert_eq!(parse("--frames").unwrap_err(), "--frames needs a value");
+    assert_eq!(parse("--palette red").unwrap_err(), "Invalid palette: red");
+    assert_eq!(parse("--colour 1").unwrap_err(), "Unknown option: --colour");
+    assert_eq!(parse("a.ch8 b.ch8").unwrap_err(), "Unexpected argument: b.ch8");
+}
+
+#[test]
+fn apply_overrides_database() {
+    let mut cpu = Cpu::new();
+    cpu.speed = 30;
+    parse("--platform chip48 --stack 4 --cycles 7 --seed 5").unwrap().apply(&mut cpu);
+    assert_eq!(cpu.quirks, Quirks { stack_depth: Some(4), ..Quirks::chip48() });
+    assert_eq!(cpu.speed, 7);
+    assert_eq!(cpu.seed, 5);
+}
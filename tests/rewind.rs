@@ -0,0 +1,29 @@
+// Rewinding frame by frame, including across changes in call depth
+use chip8::cpu::Cpu;
+use chip8::rewind::Rewind;
+
+#[test]
+fn across_call_and_return() {
+    let mut cpu = Cpu::new();
+    // CALL 206, LD V0 1, JP 204 / ADD V1 1, RET
+    cpu.load_rom(&[0x22, 0x06, 0x60, 0x01, 0x12, 0x04, 0x71, 0x01, 0x00, 0xEE]).unwrap();
+
+    let mut rewind = Rewind::new(60);
+    rewind.push(&cpu);
+    let mut frames = vec![(cpu.pc(), cpu.sp())];
+    for _ in 0..5 {
+        cpu.step(1).unwrap();
+        rewind.push(&cpu);
+        frames.push((cpu.pc(), cpu.sp()));
+    }
+    assert_eq!(rewind.len(), 5);
+    assert_eq!(cpu.v()[1], 1);
+
+    frames.pop();
+    while let Some(frame) = frames.pop() {
+        assert!(rewind.rewind(&mut cpu));
+        assert_eq!((cpu.pc(), cpu.sp()), frame);
+    }
+    assert!(!rewind.rewind(&mut cpu));
+    assert_eq!(cpu.v()[1], 0);
+}
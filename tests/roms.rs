@@ -0,0 +1,64 @@
+// Run the ROMs in roms/ headless & compare the screen with the golden images in tests/golden.
+// Regenerate the images with `UPDATE_GOLDEN=1 cargo test --test roms` after an intended change.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use chip8::cpu::Cpu;
+use chip8::headless;
+use chip8::video::MemoryVideo;
+
+// Two seconds is enough for every game to reach its title or first screen
+const FRAMES: u32 = 120;
+
+fn run(rom: &str, frames: u32) -> Result<String, String> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let mut cpu = Cpu::new();
+    cpu.seed_rng(0);
+    cpu.load_bin(&format!("{}/roms/{}", root, rom)).map_err(|e| format!("{}: {}", rom, e))?;
+    let mut video = MemoryVideo::keeping(1);
+    headless::run_frames(&mut cpu, frames, &mut video).map_err(|e| format!("{}: {}", rom, e))?;
+    Ok(headless::screen(video.last().unwrap_or(&Default::default())))
+}
+
+fn check(rom: &str, frames: u32) -> Result<(), String> {
+    let screen = run(rom, frames)?;
+    let golden = format!("{}/tests/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), rom);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &screen).expect("Failed to write golden image");
+        return Ok(());
+    }
+    let expected = fs::read_to_string(&golden).map_err(|e| format!("{}: {}", golden, e))?;
+    match headless::diff(&screen, &expected) {
+        None => Ok(()),
+        Some(diff) => Err(format!("{}: {}\n{}", rom, diff, screen)),
+    }
+}
+
+#[test]
+fn bc_test() {
+    // Prints BON when every opcode check passes, or ERROR NN
+    check("BC_test.ch8", 300).unwrap();
+}
+
+#[test]
+fn sc_test() {
+    // Prints OK when the SCHIP checks pass, or ERROR N
+    check("SC_Test.sch8", 300).unwrap();
+}
+
+#[test]
+fn rom_set() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    let mut roms: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".ch8") || name.ends_with(".sch8"))
+        .filter(|name| name != "BC_test.ch8" && name != "SC_Test.sch8")
+        .collect();
+    roms.sort();
+
+    let failures: Vec<String> = roms.iter().filter_map(|rom| check(rom, FRAMES).err()).collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,61 @@
+// Save states restore the whole machine & reject damaged files
+use chip8::cpu::{Cpu, KeyWait};
+use chip8::quirks::Quirks;
+
+// Bytes written after the key wait state: display mode, 7 quirks, stack depth, memory size, speed, vblank, seed & RNG
+const AFTER_KEY_WAIT: usize = 1 + 7 + 2 + 8 + 2 + 1 + 8 + 8;
+
+fn saved() -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.quirks = Quirks::xochip();
+    cpu.quirks.stack_depth = Some(4);
+    cpu.seed_rng(42);
+    // CALL 204, JP 200 / CALL 208, JP 204 / RND V1 FF, LD V2 K
+    cpu.load_rom(&[0x22, 0x04, 0x12, 0x00, 0x22, 0x08, 0x12, 0x04, 0xC1, 0xFF, 0xF2, 0x0A]).unwrap();
+    cpu.step(4).unwrap();
+    cpu.keypad[5] = 1;
+    cpu.step(1).unwrap();
+    assert_eq!(cpu.key_wait, KeyWait::Held(5));
+    cpu
+}
+
+#[test]
+fn round_trip() {
+    let mut cpu = saved();
+    let state = cpu.save_state();
+
+    let mut copy = Cpu::new();
+    copy.load_state(&state).unwrap();
+    assert_eq!(copy.stack(), &[0x200, 0x204]);
+    assert_eq!(copy.pc(), cpu.pc());
+    assert_eq!(copy.v(), cpu.v());
+    assert_eq!(copy.key_wait, KeyWait::Held(5));
+    assert_eq!(copy.quirks, cpu.quirks);
+    assert_eq!(copy.seed, 42);
+
+    // The RNG carries on from where it was, not from the seed
+    cpu.execute(0xC3FF).unwrap();
+    copy.execute(0xC3FF).unwrap();
+    assert_eq!(copy.v()[3], cpu.v()[3]);
+    assert_eq!(copy.save_state(), cpu.save_state());
+}
+
+#[test]
+fn bad_key_in_key_wait() {
+    let mut state = saved().save_state();
+    let key = state.len() - AFTER_KEY_WAIT - 1;
+    assert_eq!(&state[key - 1..=key], &[2, 5]);
+    state[key] = 200;
+
+    let mut cpu = Cpu::new();
+    assert!(cpu.load_state(&state).is_err());
+    assert_eq!(cpu.key_wait, KeyWait::Idle);
+}
+
+#[test]
+fn truncated() {
+    let state = saved().save_state();
+    let mut cpu = Cpu::new();
+    assert!(cpu.load_state(&state[..state.len() - 1]).is_err());
+    assert!(cpu.load_state(b"C8MV").is_err());
+}
@@ -0,0 +1,54 @@
+// Frames handed to a video backend through Cpu::render, recorded with MemoryVideo
+use chip8::cpu::Cpu;
+use chip8::headless;
+use chip8::video::{DisplayMode, MemoryVideo};
+
+#[test]
+fn render_hands_over_changed_frames() {
+    let mut cpu = Cpu::new();
+    let mut video = MemoryVideo::new();
+
+    // I points at the font's 0, drawn at 0,0
+    cpu.execute(0xA000).unwrap();
+    cpu.execute(0xD005).unwrap();
+    cpu.render(&mut video);
+    assert_eq!(video.frames.len(), 1);
+    assert_eq!(video.frames[0].pixels[0][..4], [1, 1, 1, 1]);
+    assert_eq!(video.frames[0].mode, DisplayMode::Normal);
+
+    // Nothing changed, nothing to hand over
+    cpu.render(&mut video);
+    assert_eq!(video.frames.len(), 1);
+
+    cpu.execute(0x00E0).unwrap();
+    cpu.render(&mut video);
+    assert_eq!(video.frames.len(), 2);
+    assert!(video.last().unwrap().pixels.iter().flatten().all(|&p| p == 0));
+    assert_eq!(video.frames[0].pixels[0][..4], [1, 1, 1, 1]);
+}
+
+#[test]
+fn headless_renders_each_frame() {
+    let mut cpu = Cpu::new();
+    // LD I, font 0 / DRW V0, V0, 5 / JP self
+    cpu.load_rom(&[0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04]).unwrap();
+    let mut video = MemoryVideo::new();
+    headless::run_frames(&mut cpu, 3, &mut video).unwrap();
+
+    // Drawn once in the first frame, the jump loop leaves the screen alone after that
+    assert_eq!(video.frames.len(), 1);
+    let screen = headless::screen(video.last().unwrap());
+    assert_eq!(screen.lines().count(), 32);
+    assert_eq!(screen.lines().next(), Some(format!("####{}", ".".repeat(60)).as_str()));
+}
+
+#[test]
+fn keeping_drops_old_frames() {
+    let mut cpu = Cpu::new();
+    let mut video = MemoryVideo::keeping(2);
+    for _ in 0..5 {
+        cpu.execute(0x00E0).unwrap();
+        cpu.render(&mut video);
+    }
+    assert_eq!(video.frames.len(), 2);
+}